@@ -22,6 +22,11 @@ fn main() {
         "base.html",
         "index.html",
         "post.html",
+        "page.html",
+        "about.md",
+        "tag.html",
+        "tags.html",
+        "figure.html",
         "style.css",
         "terminal_velocity.png",
     ];
@@ -0,0 +1,335 @@
+use serde::{Deserialize, Serialize};
+use siphasher::sip::SipHasher13;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::hash::Hasher;
+use std::path::{Component, Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::config::{Config, ServerConfig};
+use crate::errors::Error;
+
+// Directory inside the site directory holding build caches
+pub const CACHE_DIR: &str = ".termv-cache";
+const BUILD_CACHE_FILE: &str = "build.toml";
+// Posts as rendered from a given source, so unchanged posts skip markdown rendering
+const RENDERED_DIR: &str = "rendered";
+// Diagrams as rendered by their command, named by `DiagramRenderer::cache_key`
+pub const DIAGRAMS_DIR: &str = "diagrams";
+
+// Hashes are kept in the cache between builds, so they have to come from a
+// fixed algorithm and keys rather than std's DefaultHasher, which may change
+// between Rust releases
+pub fn hash_bytes(bytes: &[u8]) -> String {
+    let mut hasher = SipHasher13::new_with_keys(0, 0);
+    hasher.write(bytes);
+    format!("{:016x}", hasher.finish())
+}
+
+// Content hashes of the inputs behind every file in the output directory from
+// the previous build, so unchanged outputs can be skipped on the next one.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BuildCache {
+    // Hash of everything that affects every output: termv version, config and templates
+    fingerprint: String,
+    // Output path (relative to the output directory) -> hash of its inputs, or
+    // "" for outputs written on every build
+    outputs: BTreeMap<String, String>,
+    // Outputs of drafts and scheduled posts, which a build without the cache
+    // can't prune and so removes up front
+    drafts: BTreeSet<String>,
+    #[serde(skip)]
+    reused: usize,
+    // Rendered entries used by this build; the rest are removed on save
+    #[serde(skip)]
+    rendered: HashSet<String>,
+    // Outputs written or reused by this build; the rest are pruned
+    #[serde(skip)]
+    produced: HashSet<String>,
+    // Diagrams in the posts and pages of this build; the rest are removed on save
+    #[serde(skip)]
+    diagrams: HashSet<String>,
+}
+
+impl BuildCache {
+    pub fn path(config: &Config) -> PathBuf {
+        config.site_dir.join(CACHE_DIR).join(BUILD_CACHE_FILE)
+    }
+
+    // Load the cache for this site, starting from scratch when it is missing,
+    // unreadable or was written for a different config/template set.
+    pub fn load(config: &Config) -> Result<Self, Error> {
+        let fingerprint = fingerprint(config)?;
+
+        let mut cache = Self::read(config).unwrap_or_default();
+        // Recorded again as this build writes them
+        cache.drafts.clear();
+        if cache.fingerprint == fingerprint {
+            return Ok(cache);
+        }
+
+        // Nothing can be reused, but the outputs are still worth knowing about
+        // so the ones this build no longer writes get pruned
+        Ok(BuildCache {
+            fingerprint,
+            outputs: cache
+                .outputs
+                .into_keys()
+                .map(|key| (key, String::new()))
+                .collect(),
+            ..Default::default()
+        })
+    }
+
+    fn read(config: &Config) -> Option<Self> {
+        fs::read_to_string(Self::path(config))
+            .ok()
+            .and_then(|content| toml::from_str(&content).ok())
+    }
+
+    // Forget every output and rendering, for builds that run without the cache.
+    // Draft outputs are removed too, as nothing will prune them afterwards.
+    pub fn clear(config: &Config) -> Result<(), Error> {
+        if let Some(cache) = Self::read(config) {
+            let output_dir = config.output_dir();
+            for key in &cache.drafts {
+                remove_output(&output_dir, key)?;
+            }
+        }
+        let path = Self::path(config);
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        let rendered_dir = config.site_dir.join(CACHE_DIR).join(RENDERED_DIR);
+        if rendered_dir.exists() {
+            fs::remove_dir_all(rendered_dir)?;
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, config: &Config) -> Result<(), Error> {
+        let path = Self::path(config);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, toml::to_string(self)?)?;
+
+        let cache_dir = config.site_dir.join(CACHE_DIR);
+        // Renderings of deleted or edited posts, or from an older fingerprint
+        remove_unused(&cache_dir.join(RENDERED_DIR), &self.rendered)?;
+        // Diagrams of deleted posts and pages, or since edited
+        remove_unused(&cache_dir.join(DIAGRAMS_DIR), &self.diagrams)
+    }
+
+    // Where the rendering of a post whose source hashes to `source_hash` is
+    // kept. Renderings depend on the config and templates too, so the path
+    // changes with the fingerprint.
+    pub fn rendered_path(&mut self, config: &Config, source_hash: &str) -> PathBuf {
+        let key = hash_bytes(format!("{}{}", self.fingerprint, source_hash).as_bytes());
+        let path = config
+            .site_dir
+            .join(CACHE_DIR)
+            .join(RENDERED_DIR)
+            .join(format!("{}.json", key));
+        self.rendered.insert(key);
+        path
+    }
+
+    // Whether `output` was produced from inputs hashing to `hash` and still exists.
+    // Counts towards `reused` when it does.
+    pub fn is_fresh(&mut self, key: &str, hash: &str, output: &Path) -> bool {
+        let fresh =
+            !hash.is_empty() && self.outputs.get(key).is_some_and(|h| h == hash) && output.exists();
+        if fresh {
+            self.reused += 1;
+            self.produced.insert(key.to_string());
+        }
+        fresh
+    }
+
+    pub fn update(&mut self, key: String, hash: String) {
+        self.produced.insert(key.clone());
+        self.outputs.insert(key, hash);
+    }
+
+    // Note diagrams a post or page uses, so they stay cached
+    pub fn use_diagrams<'a>(&mut self, keys: impl IntoIterator<Item = &'a String>) {
+        self.diagrams.extend(keys.into_iter().cloned());
+    }
+
+    // Note an output of a draft or scheduled post
+    pub fn draft(&mut self, key: String) {
+        self.drafts.insert(key);
+    }
+
+    // Note an output that is written on every build. Its hash is cleared, so a
+    // file copied to the same path earlier never counts as fresh again.
+    pub fn produced(&mut self, key: String) {
+        self.produced.insert(key.clone());
+        self.outputs.insert(key, String::new());
+    }
+
+    // Delete the files earlier builds wrote that this one didn't, e.g. pages
+    // of deleted, renamed or drafted posts, and empty directories they leave.
+    // Returns how many files were removed.
+    pub fn prune(&mut self, output_dir: &Path) -> Result<usize, Error> {
+        let stale: Vec<String> = self
+            .outputs
+            .keys()
+            .filter(|key| !self.produced.contains(*key))
+            .cloned()
+            .collect();
+
+        let mut removed = 0;
+        for key in stale {
+            self.outputs.remove(&key);
+            if remove_output(output_dir, &key)? {
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    pub fn reused(&self) -> usize {
+        self.reused
+    }
+}
+
+// Delete the files in `dir` whose names (less the extension) aren't in `used`
+fn remove_unused(dir: &Path, used: &HashSet<String>) -> Result<(), Error> {
+    for entry in fs::read_dir(dir).into_iter().flatten() {
+        let entry = entry?;
+        let key = entry
+            .path()
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned());
+        if !key.is_some_and(|key| used.contains(&key)) {
+            fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+// Delete an output recorded under `key` and the empty directories it leaves.
+// Returns whether there was a file to delete.
+fn remove_output(output_dir: &Path, key: &str) -> Result<bool, Error> {
+    // Keys come from a file on disk; never follow one out of the output directory
+    let relative = Path::new(key);
+    if !relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+    {
+        return Ok(false);
+    }
+
+    let path = output_dir.join(relative);
+    let removed = path.is_file();
+    if removed {
+        fs::remove_file(&path)?;
+    }
+    for dir in path.ancestors().skip(1) {
+        if dir == output_dir || fs::remove_dir(dir).is_err() {
+            break;
+        }
+    }
+    Ok(removed)
+}
+
+// Changes to the config, any template, shortcode component or syntax definition
+// can affect every page, so they invalidate the whole cache
+fn fingerprint(config: &Config) -> Result<String, Error> {
+    // Settings that change how or which posts are built, but not what any one
+    // cached output looks like, so `-v`, `--jobs` or `serve --drafts` reuse it
+    let mut config = config.clone();
+    config.build.verbose = false;
+    config.build.jobs = None;
+    config.build.cache = true;
+    config.build.drafts = false;
+    config.build.future = false;
+    config.server = ServerConfig::default();
+
+    let mut inputs = Vec::new();
+    inputs.extend_from_slice(env!("CARGO_PKG_VERSION").as_bytes());
+    inputs.extend_from_slice(toml::to_string(&config)?.as_bytes());
+
+    for dir in [
+        config.templates_dir(),
+        config.components_dir(),
+        config.syntaxes_dir(),
+    ] {
+        for entry in WalkDir::new(&dir)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| e.file_type().is_file())
+        {
+            inputs.extend_from_slice(entry.path().to_string_lossy().as_bytes());
+            inputs.extend_from_slice(&fs::read(entry.path())?);
+        }
+    }
+
+    Ok(hash_bytes(&inputs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{create_test_config, setup_test_site};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_hash_bytes_is_stable() {
+        // Changing these invalidates every cache written by earlier releases
+        assert_eq!(hash_bytes(b""), hash_bytes(&[]));
+        assert_eq!(hash_bytes(b"termv"), "f5338cd841bbae78");
+        assert_ne!(hash_bytes(b"termv"), hash_bytes(b"termV"));
+    }
+
+    #[test]
+    fn test_cache_roundtrip_and_invalidation() -> Result<(), Error> {
+        let temp_dir = TempDir::new()?;
+        setup_test_site(&temp_dir)?;
+        let config = create_test_config(&temp_dir);
+
+        let output = temp_dir.path().join("out.html");
+        fs::write(&output, "cached")?;
+
+        let mut cache = BuildCache::load(&config)?;
+        assert!(!cache.is_fresh("out.html", "abc", &output));
+        cache.update("out.html".to_string(), "abc".to_string());
+        cache.save(&config)?;
+
+        let mut cache = BuildCache::load(&config)?;
+        assert!(cache.is_fresh("out.html", "abc", &output));
+        assert!(!cache.is_fresh("out.html", "def", &output));
+        assert_eq!(cache.reused(), 1);
+
+        // Once something else writes the path, the copy's hash no longer applies
+        cache.produced("out.html".to_string());
+        assert!(!cache.is_fresh("out.html", "abc", &output));
+
+        // Settings that don't change any output keep the cache
+        let mut quiet_change = config.clone();
+        quiet_change.build.verbose = true;
+        quiet_change.build.jobs = Some(2);
+        quiet_change.build.drafts = true;
+        quiet_change.server.port = 4000;
+        let mut cache = BuildCache::load(&quiet_change)?;
+        assert!(cache.is_fresh("out.html", "abc", &output));
+
+        let mut config_change = config.clone();
+        config_change.title = "Renamed".to_string();
+        let mut cache = BuildCache::load(&config_change)?;
+        assert!(!cache.is_fresh("out.html", "abc", &output));
+
+        // Template changes throw the whole cache away
+        fs::write(
+            temp_dir.path().join("templates/base.html"),
+            "<main>{% block content %}{% endblock %}</main>",
+        )?;
+        let mut cache = BuildCache::load(&config)?;
+        assert!(!cache.is_fresh("out.html", "abc", &output));
+        Ok(())
+    }
+}
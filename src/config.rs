@@ -1,7 +1,11 @@
 use crate::errors::Error;
+use crate::permalink;
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
+use tera::Value;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(default)]
@@ -11,9 +15,17 @@ pub struct Config {
     pub base_url: String,
     pub title: String,
     pub description: String,
+    // IANA name such as "Europe/Berlin" for post dates written without an offset (UTC if unset)
+    pub timezone: Option<String>,
+    // Where posts are published, e.g. "/:year/:month/:slug/" (see `permalink`)
+    pub permalink: String,
     pub author: Author,
     pub build: BuildConfig,
     pub server: ServerConfig,
+    pub feed: FeedConfig,
+    pub markdown: MarkdownConfig,
+    // Site-wide custom values for templates, as `config.extra`
+    pub extra: BTreeMap<String, Value>,
 }
 
 impl Default for Config {
@@ -23,9 +35,14 @@ impl Default for Config {
             base_url: "http://localhost".into(),
             title: "My Terminal Velocity Blog".into(),
             description: "A blazingly fast tech blog".into(),
+            timezone: None,
+            permalink: permalink::DEFAULT.into(),
             author: Author::default(),
             build: BuildConfig::default(),
             server: ServerConfig::default(),
+            feed: FeedConfig::default(),
+            markdown: MarkdownConfig::default(),
+            extra: BTreeMap::new(),
         }
     }
 }
@@ -52,9 +69,26 @@ pub struct BuildConfig {
     pub verbose: bool,
     pub output_dir: String,
     pub posts_dir: String,
+    pub pages_dir: String,
     pub templates_dir: String,
+    // Tera templates for shortcodes used in markdown
+    pub components_dir: String,
+    // Extra .sublime-syntax definitions for code highlighting
+    pub syntaxes_dir: String,
     pub static_dir: String,
     pub post_assets_dir: String,
+    // Number of posts per listing page; unset or 0 renders a single page
+    pub paginate_by: Option<usize>,
+    // Include posts marked `draft: true`
+    pub drafts: bool,
+    // Include posts dated after the time of the build
+    pub future: bool,
+    // Skip re-rendering and re-copying outputs whose inputs are unchanged since the last build
+    pub cache: bool,
+    // Number of threads used to render posts; unset uses every core
+    pub jobs: Option<usize>,
+    // Date posts without an `updated` field by the last commit that changed them
+    pub updated_from_git: bool,
 }
 
 impl Default for BuildConfig {
@@ -63,9 +97,18 @@ impl Default for BuildConfig {
             verbose: false,
             output_dir: "dist".into(),
             posts_dir: "posts".into(),
+            pages_dir: "pages".into(),
             templates_dir: "templates".into(),
+            components_dir: "components".into(),
+            syntaxes_dir: "syntaxes".into(),
             static_dir: "static".into(),
             post_assets_dir: "assets".into(),
+            paginate_by: None,
+            drafts: false,
+            future: false,
+            cache: true,
+            jobs: None,
+            updated_from_git: false,
         }
     }
 }
@@ -88,6 +131,42 @@ impl Default for ServerConfig {
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct FeedConfig {
+    pub enabled: bool,
+    // Maximum number of posts in each feed; 0 includes every post
+    pub limit: usize,
+    // Embed the rendered post body instead of just the preview
+    pub full_content: bool,
+}
+
+impl Default for FeedConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            limit: 20,
+            full_content: false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct MarkdownConfig {
+    // Bundled syntect theme name, or path to a .tmTheme file, used to write css/syntax.css
+    pub syntax_theme: Option<String>,
+    // Theme used instead when the browser prefers a dark colour scheme
+    pub syntax_theme_dark: Option<String>,
+    // Code fence languages highlighted as another syntax, e.g. tf = "hcl"
+    pub language_aliases: BTreeMap<String, String>,
+    // Code fence languages rendered by a local command, e.g. dot = "dot -Tsvg"
+    pub diagrams: BTreeMap<String, String>,
+    // Render `$...$` and `$$...$$` as math. Off by default, as posts mentioning
+    // prices or shell variables would otherwise lose their dollar signs
+    pub math: bool,
+}
+
 #[derive(Default)]
 pub struct ConfigOverrides {
     pub port: Option<u16>,
@@ -96,6 +175,10 @@ pub struct ConfigOverrides {
     pub output_dir: Option<PathBuf>,
     pub author: Option<String>,
     pub auto_build: Option<bool>,
+    pub drafts: Option<bool>,
+    pub future: Option<bool>,
+    pub cache: Option<bool>,
+    pub jobs: Option<usize>,
 }
 
 impl Config {
@@ -109,6 +192,11 @@ impl Config {
             Config::default()
         };
 
+        // Catch a misspelled timezone before any post is read
+        config.timezone()?;
+        permalink::validate_site_pattern(&config.permalink)
+            .map_err(|message| Error::ConfigParse(format!("permalink: {}", message)))?;
+
         // Always store absolute path for site_dir
         config.site_dir = site_dir
             .canonicalize()
@@ -116,6 +204,15 @@ impl Config {
         Ok(config)
     }
 
+    pub fn timezone(&self) -> Result<Tz, Error> {
+        match &self.timezone {
+            Some(name) => name
+                .parse()
+                .map_err(|_| Error::ConfigParse(format!("unknown timezone `{}`", name))),
+            None => Ok(Tz::UTC),
+        }
+    }
+
     pub fn with_overrides(mut self, overrides: ConfigOverrides) -> Self {
         if let Some(port) = overrides.port {
             self.server.port = port;
@@ -132,6 +229,18 @@ impl Config {
         if let Some(author) = overrides.author {
             self.author.name = author;
         }
+        if let Some(drafts) = overrides.drafts {
+            self.build.drafts = drafts;
+        }
+        if let Some(future) = overrides.future {
+            self.build.future = future;
+        }
+        if let Some(cache) = overrides.cache {
+            self.build.cache = cache;
+        }
+        if let Some(jobs) = overrides.jobs {
+            self.build.jobs = Some(jobs);
+        }
         if let Some(output_dir) = overrides.output_dir {
             self.build.output_dir = output_dir.to_string_lossy().into();
         }
@@ -152,6 +261,18 @@ impl Config {
         self.resolve_path(&self.build.posts_dir)
     }
 
+    pub fn pages_dir(&self) -> PathBuf {
+        self.resolve_path(&self.build.pages_dir)
+    }
+
+    pub fn components_dir(&self) -> PathBuf {
+        self.resolve_path(&self.build.components_dir)
+    }
+
+    pub fn syntaxes_dir(&self) -> PathBuf {
+        self.resolve_path(&self.build.syntaxes_dir)
+    }
+
     pub fn output_dir(&self) -> PathBuf {
         self.resolve_path(&self.build.output_dir)
     }
@@ -164,6 +285,15 @@ impl Config {
         self.resolve_path(&self.build.static_dir)
     }
 
+    // Turn a site-relative URL path like "/posts/foo/" into an absolute URL on base_url
+    pub fn absolute_url(&self, path: &str) -> String {
+        format!(
+            "{}/{}",
+            self.base_url.trim_end_matches('/'),
+            path.trim_start_matches('/')
+        )
+    }
+
     pub fn get_absolute_path(&self, path: &Path) -> PathBuf {
         if path.is_absolute() {
             path.to_path_buf()
@@ -189,6 +319,7 @@ mod tests {
 
         // Test relative paths
         assert_eq!(config.posts_dir(), absolute_path.join("posts"));
+        assert_eq!(config.pages_dir(), absolute_path.join("pages"));
         assert_eq!(config.output_dir(), absolute_path.join("dist"));
 
         // Test absolute paths
@@ -221,4 +352,23 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_absolute_url() {
+        let mut config = Config {
+            base_url: "https://example.com/".into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.absolute_url("/posts/foo/"),
+            "https://example.com/posts/foo/"
+        );
+
+        config.base_url = "https://example.com/blog".into();
+        assert_eq!(
+            config.absolute_url("rss.xml"),
+            "https://example.com/blog/rss.xml"
+        );
+        assert_eq!(config.absolute_url("/"), "https://example.com/blog/");
+    }
 }
@@ -0,0 +1,282 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::de::{self, value::MapAccessDeserializer, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use tera::Value;
+
+// When a post was published: a plain date, a local date and time, or an
+// RFC 3339 datetime with an offset. The first two are in the site's `timezone`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PostDate {
+    datetime: DateTime<FixedOffset>,
+    // Written as a plain date, so shown without a time
+    has_time: bool,
+    // Written without an offset, and not yet pinned to a timezone
+    floating: bool,
+}
+
+// Local datetimes accepted besides RFC 3339, most specific first
+const LOCAL_FORMATS: [&str; 4] = [
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M",
+];
+
+impl PostDate {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let input = input.trim();
+        let offset_formats = ["%Y-%m-%d %H:%M:%S%.f %:z", "%Y-%m-%d %H:%M:%S%.f %z"];
+        if let Some(datetime) = DateTime::parse_from_rfc3339(input).ok().or_else(|| {
+            offset_formats
+                .iter()
+                .find_map(|format| DateTime::parse_from_str(input, format).ok())
+        }) {
+            return Ok(Self {
+                datetime,
+                has_time: true,
+                floating: false,
+            });
+        }
+
+        let local = LOCAL_FORMATS
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(input, format).ok())
+            .map(|local| (local, true))
+            .or_else(|| {
+                NaiveDate::parse_from_str(input, "%Y-%m-%d")
+                    .ok()
+                    .and_then(|date| date.and_hms_opt(0, 0, 0))
+                    .map(|local| (local, false))
+            });
+
+        match local {
+            Some((local, has_time)) => Ok(Self {
+                datetime: local.and_utc().fixed_offset(),
+                has_time,
+                floating: true,
+            }),
+            None => Err(format!(
+                "invalid date `{}`, expected YYYY-MM-DD or an RFC 3339 datetime (e.g. 2024-11-19 or 2024-11-19T09:30:00+01:00)",
+                input
+            )),
+        }
+    }
+
+    // A datetime known in full, e.g. a commit time
+    pub fn from_datetime<Z: TimeZone>(datetime: DateTime<Z>) -> Self {
+        Self {
+            datetime: datetime.fixed_offset(),
+            has_time: true,
+            floating: false,
+        }
+    }
+
+    // Pin a date written without an offset to `timezone`
+    pub fn in_timezone(self, timezone: Tz) -> Self {
+        if !self.floating {
+            return self;
+        }
+        let local = self.datetime.naive_local();
+        // A local time skipped by a DST change is read as UTC rather than rejected
+        let datetime = timezone
+            .from_local_datetime(&local)
+            .earliest()
+            .unwrap_or_else(|| timezone.from_utc_datetime(&local));
+        Self {
+            datetime: datetime.fixed_offset(),
+            has_time: self.has_time,
+            floating: false,
+        }
+    }
+
+    pub fn datetime(&self) -> DateTime<FixedOffset> {
+        self.datetime
+    }
+
+    pub fn date_naive(&self) -> NaiveDate {
+        self.datetime.date_naive()
+    }
+}
+
+impl fmt::Display for PostDate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.has_time {
+            f.write_str(&self.datetime.to_rfc3339())
+        } else {
+            write!(f, "{}", self.datetime.format("%Y-%m-%d"))
+        }
+    }
+}
+
+impl FromStr for PostDate {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, String> {
+        Self::parse(input)
+    }
+}
+
+// Templates see the date as written: "2024-11-19", or RFC 3339 when it has a time
+impl Serialize for PostDate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for PostDate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct DateVisitor;
+
+        impl<'de> Visitor<'de> for DateVisitor {
+            type Value = PostDate;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a date (YYYY-MM-DD) or datetime")
+            }
+
+            fn visit_str<E: de::Error>(self, input: &str) -> Result<PostDate, E> {
+                PostDate::parse(input).map_err(E::custom)
+            }
+
+            // TOML frontmatter has date types of its own, e.g. `date = 2024-01-01`
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<PostDate, A::Error> {
+                let date = toml::value::Datetime::deserialize(MapAccessDeserializer::new(map))?;
+                self.visit_str(&date.to_string())
+            }
+        }
+
+        deserializer.deserialize_any(DateVisitor)
+    }
+}
+
+// Shadows Tera's `date` filter so dates are shown in the site's timezone unless
+// the template passes one: `{{ post.metadata.date | date(format="%B %-d, %Y") }}`
+pub fn date_filter(
+    site_timezone: Tz,
+) -> impl Fn(&Value, &HashMap<String, Value>) -> tera::Result<Value> + Send + Sync {
+    move |value, args| {
+        let format = match args.get("format") {
+            Some(format) => tera::from_value::<String>(format.clone())?,
+            None => "%Y-%m-%d".to_string(),
+        };
+        if StrftimeItems::new(&format).any(|item| matches!(item, Item::Error)) {
+            return Err(tera::Error::msg(format!(
+                "Invalid date format `{}`",
+                format
+            )));
+        }
+
+        let timezone = match args.get("timezone") {
+            Some(name) => {
+                let name = tera::from_value::<String>(name.clone())?;
+                name.parse::<Tz>()
+                    .map_err(|_| tera::Error::msg(format!("Unknown timezone `{}`", name)))?
+            }
+            None => site_timezone,
+        };
+
+        let datetime = match value {
+            Value::String(input) => PostDate::parse(input)
+                .map_err(tera::Error::msg)?
+                .in_timezone(timezone)
+                .datetime(),
+            // Unix timestamps, e.g. from `now(timestamp=true)`
+            Value::Number(seconds) => seconds
+                .as_i64()
+                .and_then(|seconds| DateTime::<Utc>::from_timestamp(seconds, 0))
+                .ok_or_else(|| tera::Error::msg(format!("Invalid timestamp `{}`", seconds)))?
+                .fixed_offset(),
+            other => {
+                return Err(tera::Error::msg(format!(
+                    "Filter `date` expects a date string or timestamp, got `{}`",
+                    other
+                )))
+            }
+        };
+
+        Ok(Value::String(
+            datetime
+                .with_timezone(&timezone)
+                .format(&format)
+                .to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_formats() {
+        let date: PostDate = "2024-11-19".parse().unwrap();
+        assert_eq!(date.to_string(), "2024-11-19");
+        assert_eq!(
+            date.date_naive(),
+            NaiveDate::from_ymd_opt(2024, 11, 19).unwrap()
+        );
+
+        let date: PostDate = "2024-11-19T09:30:00+01:00".parse().unwrap();
+        assert_eq!(date.to_string(), "2024-11-19T09:30:00+01:00");
+
+        let date: PostDate = "2024-11-19 09:30".parse().unwrap();
+        assert_eq!(date.to_string(), "2024-11-19T09:30:00+00:00");
+
+        for invalid in ["2024/11/19", "19-11-2024", "2024-13-01", "soon"] {
+            assert!(invalid.parse::<PostDate>().unwrap_err().contains(invalid));
+        }
+    }
+
+    #[test]
+    fn test_timezone_and_ordering() {
+        let berlin: Tz = "Europe/Berlin".parse().unwrap();
+        let local = PostDate::parse("2024-07-01 09:00")
+            .unwrap()
+            .in_timezone(berlin);
+        assert_eq!(local.to_string(), "2024-07-01T09:00:00+02:00");
+
+        // Offsets written in the post win over the site timezone
+        let explicit = PostDate::parse("2024-07-01T08:30:00Z")
+            .unwrap()
+            .in_timezone(berlin);
+        assert_eq!(explicit.to_string(), "2024-07-01T08:30:00+00:00");
+        assert!(local < explicit);
+
+        let midnight = PostDate::parse("2024-07-01").unwrap().in_timezone(berlin);
+        assert_eq!(midnight.to_string(), "2024-07-01");
+        assert_eq!(
+            midnight.datetime().to_rfc3339(),
+            "2024-07-01T00:00:00+02:00"
+        );
+        assert!(midnight < local);
+    }
+
+    #[test]
+    fn test_date_filter() {
+        let filter = date_filter("America/New_York".parse().unwrap());
+        let format = |format: &str| HashMap::from([("format".to_string(), Value::from(format))]);
+
+        let value = Value::from("2024-07-01T12:00:00Z");
+        assert_eq!(filter(&value, &format("%H:%M %Z")).unwrap(), "08:00 EDT");
+
+        let mut args = format("%H:%M");
+        args.insert("timezone".to_string(), Value::from("UTC"));
+        assert_eq!(filter(&value, &args).unwrap(), "12:00");
+
+        let value = Value::from("2024-07-01");
+        assert_eq!(
+            filter(&value, &format("%B %-d, %Y")).unwrap(),
+            "July 1, 2024"
+        );
+        assert_eq!(
+            filter(&Value::from(0), &HashMap::new()).unwrap(),
+            "1969-12-31"
+        );
+        assert!(filter(&value, &format("%Q")).is_err());
+    }
+}
@@ -0,0 +1,233 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::cache::hash_bytes;
+
+// Turns the source of a diagram code block (```dot, ```mermaid, ...) into
+// markup, usually inline SVG
+pub trait DiagramRenderer: Send + Sync {
+    fn render(&self, source: &str) -> Result<String, String>;
+
+    // The key the rendering of `source` is cached under, if renderings are cached
+    fn cache_key(&self, _source: &str) -> Option<String> {
+        None
+    }
+}
+
+// Runs a local command for each diagram. The source is written to its stdin
+// and the SVG read from its stdout, unless the command names files with
+// `{input}` / `{output}`, e.g. `mmdc -i {input} -o {output}`.
+pub struct CommandRenderer {
+    command: String,
+    // Rendered diagrams by content hash, so unchanged diagrams skip the command
+    cache_dir: Option<PathBuf>,
+}
+
+impl CommandRenderer {
+    pub fn new(command: &str, cache_dir: Option<PathBuf>) -> Self {
+        Self {
+            command: command.to_string(),
+            cache_dir,
+        }
+    }
+
+    fn key(&self, source: &str) -> String {
+        hash_bytes(format!("{}\0{}", self.command, source).as_bytes())
+    }
+
+    fn run(&self, source: &str, key: &str) -> Result<String, String> {
+        let mut args = shell_words::split(&self.command)
+            .map_err(|e| format!("invalid command `{}`: {}", self.command, e))?;
+        if args.is_empty() {
+            return Err("diagram command is empty".to_string());
+        }
+
+        let uses_input = args.iter().any(|a| a.contains("{input}"));
+        let uses_output = args.iter().any(|a| a.contains("{output}"));
+        let work_dir = if uses_input || uses_output {
+            Some(WorkDir::new(key).map_err(|e| e.to_string())?)
+        } else {
+            None
+        };
+
+        if let Some(work_dir) = &work_dir {
+            for arg in &mut args {
+                *arg = arg
+                    .replace("{input}", &work_dir.input().to_string_lossy())
+                    .replace("{output}", &work_dir.output().to_string_lossy());
+            }
+            if uses_input {
+                fs::write(work_dir.input(), source).map_err(|e| e.to_string())?;
+            }
+        }
+
+        let mut child = Command::new(&args[0])
+            .args(&args[1..])
+            .stdin(if uses_input {
+                Stdio::null()
+            } else {
+                Stdio::piped()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("failed to run `{}`: {}", args[0], e))?;
+
+        // The source is fed from another thread while the output is read, as a
+        // command may fill its stdout pipe before it has read all of its input
+        let result = thread::scope(|scope| {
+            if let Some(mut stdin) = child.stdin.take() {
+                // A command that exits early is reported through its exit status below
+                scope.spawn(move || {
+                    let _ = stdin.write_all(source.as_bytes());
+                });
+            }
+            child.wait_with_output()
+        })
+        .map_err(|e| e.to_string())?;
+        if !result.status.success() {
+            return Err(format!(
+                "`{}` failed ({}): {}",
+                self.command,
+                result.status,
+                String::from_utf8_lossy(&result.stderr).trim()
+            ));
+        }
+
+        let svg = match &work_dir {
+            Some(work_dir) if uses_output => fs::read_to_string(work_dir.output())
+                .map_err(|e| format!("`{}` wrote no {{output}}: {}", self.command, e))?,
+            _ => String::from_utf8_lossy(&result.stdout).into_owned(),
+        };
+        Ok(strip_prolog(&svg).to_string())
+    }
+}
+
+impl DiagramRenderer for CommandRenderer {
+    fn render(&self, source: &str) -> Result<String, String> {
+        let key = self.key(source);
+        let cached = self
+            .cache_dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}.svg", key)));
+
+        if let Some(svg) = cached
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+        {
+            return Ok(svg);
+        }
+
+        let svg = self.run(source, &key)?;
+
+        if let Some(path) = cached {
+            // A failed write only costs a re-render next time
+            if let Some(parent) = path.parent() {
+                let _ = fs::create_dir_all(parent);
+            }
+            let _ = fs::write(path, &svg);
+        }
+        Ok(svg)
+    }
+
+    fn cache_key(&self, source: &str) -> Option<String> {
+        self.cache_dir.as_ref().map(|_| self.key(source))
+    }
+}
+
+// Drop the XML declaration and doctype, which aren't allowed inside HTML
+fn strip_prolog(svg: &str) -> &str {
+    let svg = svg.trim();
+    match svg.find("<svg") {
+        Some(start) => &svg[start..],
+        None => svg,
+    }
+}
+
+// Scratch directory for commands that read and write files, removed when dropped
+struct WorkDir {
+    path: PathBuf,
+}
+
+impl WorkDir {
+    fn new(key: &str) -> std::io::Result<Self> {
+        let path = std::env::temp_dir().join(format!(
+            "termv-diagram-{}-{}-{:?}",
+            key,
+            std::process::id(),
+            std::thread::current().id()
+        ));
+        fs::create_dir_all(&path)?;
+        Ok(Self { path })
+    }
+
+    fn input(&self) -> PathBuf {
+        self.path.join("input")
+    }
+
+    fn output(&self) -> PathBuf {
+        self.path.join("output.svg")
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_command_renderer_uses_stdin_and_cache() {
+        let temp_dir = TempDir::new().unwrap();
+        let log = temp_dir.path().join("runs.log");
+        let command = format!("sh -c 'echo run >> {}; cat'", log.display());
+        let renderer = CommandRenderer::new(&command, Some(temp_dir.path().join("diagrams")));
+
+        let source = "<?xml version=\"1.0\"?>\n<svg><text>a</text></svg>\n";
+        assert_eq!(
+            renderer.render(source).unwrap(),
+            "<svg><text>a</text></svg>"
+        );
+        assert_eq!(
+            renderer.render(source).unwrap(),
+            "<svg><text>a</text></svg>"
+        );
+        assert_eq!(fs::read_to_string(&log).unwrap(), "run\n");
+    }
+
+    #[test]
+    fn test_command_renderer_large_output() {
+        // Writes more than a pipe holds before reading any input
+        let renderer = CommandRenderer::new("sh -c 'yes | head -c 1000000; cat >/dev/null'", None);
+        let svg = renderer.render(&"y".repeat(1_000_000)).unwrap();
+        assert_eq!(svg.matches('y').count(), 500_000);
+    }
+
+    #[test]
+    fn test_command_renderer_files() {
+        let renderer = CommandRenderer::new("cp {input} {output}", None);
+        assert_eq!(renderer.render("<svg/>").unwrap(), "<svg/>");
+    }
+
+    #[test]
+    fn test_command_renderer_failure() {
+        let renderer = CommandRenderer::new("sh -c 'echo bad syntax >&2; exit 3'", None);
+        let err = renderer.render("digraph {").unwrap_err();
+        assert!(err.contains("failed"));
+        assert!(err.contains("bad syntax"));
+
+        let renderer = CommandRenderer::new("termv-no-such-command", None);
+        assert!(renderer
+            .render("x")
+            .unwrap_err()
+            .contains("failed to run `termv-no-such-command`"));
+    }
+}
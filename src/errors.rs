@@ -1,6 +1,7 @@
+use std::fmt;
 use std::path::PathBuf;
 
-#[derive(thiserror::Error, Debug)]
+#[derive(thiserror::Error)]
 pub enum Error {
     #[error("Directory not found: {0}")]
     DirectoryNotFound(PathBuf),
@@ -14,11 +15,52 @@ pub enum Error {
     #[error("Template error: {0}")]
     Template(#[from] tera::Error),
 
-    #[error("Frontmatter error in {file}: {message}")]
-    Frontmatter { file: String, message: String },
+    #[error("Frontmatter error in {file}:{line}: {message}")]
+    Frontmatter {
+        file: String,
+        line: usize,
+        message: String,
+    },
+
+    #[error("Shortcode error in {file}:{line}: {message}")]
+    Shortcode {
+        file: String,
+        line: usize,
+        message: String,
+    },
+
+    #[error("Math error in {file}:{line}: {message}")]
+    Math {
+        file: String,
+        line: usize,
+        message: String,
+    },
+
+    #[error("Diagram error in {file}:{line}: {message}")]
+    Diagram {
+        file: String,
+        line: usize,
+        message: String,
+    },
+
+    #[error("Heading error in {file}:{line}: {message}")]
+    Heading {
+        file: String,
+        line: usize,
+        message: String,
+    },
+
+    #[error("Permalink error: {0}")]
+    Permalink(String),
+
+    #[error("Syntax theme error: {0}")]
+    SyntaxTheme(String),
+
+    #[error("Syntax definition error: {0}")]
+    SyntaxDefinition(String),
 
     #[error("Server error: {0}")]
-    Server(#[from] Box<dyn std::error::Error>),
+    Server(String),
 
     #[error("Configuration parse error: {0}")]
     ConfigParse(String),
@@ -38,6 +80,54 @@ pub enum Error {
     #[error("TOML serialization error: {0}")]
     TomlSer(#[from] toml::ser::Error),
 
+    // Everything that went wrong reading the site's content, reported together
+    #[error("{} errors:\n{}", .0.len(), list_errors(.0))]
+    Multiple(Vec<Error>),
+
     #[error(transparent)]
     Other(#[from] Box<dyn std::error::Error + Send + Sync>),
 }
+
+impl Error {
+    // A single error as-is, several as `Multiple` (flattening nested lists)
+    pub fn many(errors: Vec<Error>) -> Error {
+        let mut flat: Vec<Error> = errors
+            .into_iter()
+            .flat_map(|e| match e {
+                Error::Multiple(inner) => inner,
+                e => vec![e],
+            })
+            .collect();
+        if flat.len() == 1 {
+            flat.remove(0)
+        } else {
+            Error::Multiple(flat)
+        }
+    }
+}
+
+// `main` hands errors back through `?`, which prints them with Debug, so
+// show the message (with any cause it leaves out) rather than the variant
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = self.to_string();
+        write!(f, "{}", message)?;
+        let mut source = std::error::Error::source(self);
+        while let Some(cause) = source {
+            let cause_message = cause.to_string();
+            if !message.contains(&cause_message) {
+                write!(f, "\n  caused by: {}", cause_message)?;
+            }
+            source = cause.source();
+        }
+        Ok(())
+    }
+}
+
+fn list_errors(errors: &[Error]) -> String {
+    errors
+        .iter()
+        .map(|e| e.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,290 @@
+use chrono::Utc;
+
+use crate::config::Config;
+use crate::post::Post;
+
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Rewrite root-relative links (e.g. post assets) so they resolve from a feed reader
+fn absolutize_links(html: &str, config: &Config) -> String {
+    let base = config.base_url.trim_end_matches('/');
+    html.replace("src=\"/", &format!("src=\"{}/", base))
+        .replace("href=\"/", &format!("href=\"{}/", base))
+}
+
+fn feed_posts<'a>(config: &Config, posts: &'a [Post]) -> Vec<&'a Post> {
+    let published = posts.iter().filter(|post| !post.metadata.draft);
+    match config.feed.limit {
+        0 => published.collect(),
+        limit => published.take(limit).collect(),
+    }
+}
+
+fn item_content(config: &Config, post: &Post) -> String {
+    if config.feed.full_content {
+        absolutize_links(&post.html_content, config)
+    } else if post.metadata.preview.is_empty() {
+        absolutize_links(&post.summary, config)
+    } else {
+        post.metadata.preview.clone()
+    }
+}
+
+// Build an RSS 2.0 document from posts already sorted newest first
+pub fn rss(config: &Config, posts: &[Post]) -> String {
+    let posts = feed_posts(config, posts);
+    let mut xml = String::new();
+
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\">\n<channel>\n");
+    xml.push_str(&format!("<title>{}</title>\n", escape_xml(&config.title)));
+    xml.push_str(&format!(
+        "<link>{}</link>\n",
+        escape_xml(&config.absolute_url("/"))
+    ));
+    xml.push_str(&format!(
+        "<description>{}</description>\n",
+        escape_xml(&config.description)
+    ));
+    xml.push_str(&format!(
+        "<atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>\n",
+        escape_xml(&config.absolute_url("/rss.xml"))
+    ));
+    xml.push_str("<generator>Terminal Velocity</generator>\n");
+    if let Some(latest) = posts.first() {
+        xml.push_str(&format!(
+            "<lastBuildDate>{}</lastBuildDate>\n",
+            latest.metadata.date.datetime().to_rfc2822()
+        ));
+    }
+
+    for post in posts {
+        let url = escape_xml(&config.absolute_url(&post.url));
+        xml.push_str("<item>\n");
+        xml.push_str(&format!(
+            "<title>{}</title>\n",
+            escape_xml(&post.metadata.title)
+        ));
+        xml.push_str(&format!("<link>{}</link>\n", url));
+        xml.push_str(&format!("<guid isPermaLink=\"true\">{}</guid>\n", url));
+        xml.push_str(&format!(
+            "<pubDate>{}</pubDate>\n",
+            post.metadata.date.datetime().to_rfc2822()
+        ));
+        xml.push_str(&format!(
+            "<author>{} ({})</author>\n",
+            escape_xml(&config.author.email),
+            escape_xml(&post.metadata.author)
+        ));
+        for tag in &post.metadata.tags {
+            xml.push_str(&format!("<category>{}</category>\n", escape_xml(tag)));
+        }
+        xml.push_str(&format!(
+            "<description>{}</description>\n",
+            escape_xml(&item_content(config, post))
+        ));
+        xml.push_str("</item>\n");
+    }
+
+    xml.push_str("</channel>\n</rss>\n");
+    xml
+}
+
+// Build an Atom 1.0 document from posts already sorted newest first
+pub fn atom(config: &Config, posts: &[Post]) -> String {
+    let posts = feed_posts(config, posts);
+    let mut xml = String::new();
+
+    let updated = posts
+        .iter()
+        .map(|post| post.last_modified().datetime())
+        .max()
+        .unwrap_or_else(|| Utc::now().fixed_offset())
+        .to_rfc3339();
+
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str(&format!("<title>{}</title>\n", escape_xml(&config.title)));
+    xml.push_str(&format!(
+        "<subtitle>{}</subtitle>\n",
+        escape_xml(&config.description)
+    ));
+    xml.push_str(&format!(
+        "<link href=\"{}\"/>\n",
+        escape_xml(&config.absolute_url("/"))
+    ));
+    xml.push_str(&format!(
+        "<link href=\"{}\" rel=\"self\" type=\"application/atom+xml\"/>\n",
+        escape_xml(&config.absolute_url("/atom.xml"))
+    ));
+    xml.push_str(&format!(
+        "<id>{}</id>\n",
+        escape_xml(&config.absolute_url("/"))
+    ));
+    xml.push_str(&format!("<updated>{}</updated>\n", updated));
+    xml.push_str(&format!(
+        "<author>\n<name>{}</name>\n<email>{}</email>\n</author>\n",
+        escape_xml(&config.author.name),
+        escape_xml(&config.author.email)
+    ));
+    xml.push_str("<generator>Terminal Velocity</generator>\n");
+
+    for post in posts {
+        let url = escape_xml(&config.absolute_url(&post.url));
+        let date = post.metadata.date.datetime().to_rfc3339();
+        let updated = post.last_modified().datetime().to_rfc3339();
+
+        xml.push_str("<entry>\n");
+        xml.push_str(&format!(
+            "<title>{}</title>\n",
+            escape_xml(&post.metadata.title)
+        ));
+        xml.push_str(&format!("<link href=\"{}\"/>\n", url));
+        xml.push_str(&format!("<id>{}</id>\n", url));
+        xml.push_str(&format!("<published>{}</published>\n", date));
+        xml.push_str(&format!("<updated>{}</updated>\n", updated));
+        xml.push_str(&format!(
+            "<author>\n<name>{}</name>\n</author>\n",
+            escape_xml(&post.metadata.author)
+        ));
+        for tag in &post.metadata.tags {
+            xml.push_str(&format!("<category term=\"{}\"/>\n", escape_xml(tag)));
+        }
+        if !post.metadata.preview.is_empty() {
+            xml.push_str(&format!(
+                "<summary>{}</summary>\n",
+                escape_xml(&post.metadata.preview)
+            ));
+        } else if !post.summary.is_empty() {
+            xml.push_str(&format!(
+                "<summary type=\"html\">{}</summary>\n",
+                escape_xml(&absolutize_links(&post.summary, config))
+            ));
+        }
+        if config.feed.full_content {
+            xml.push_str(&format!(
+                "<content type=\"html\">{}</content>\n",
+                escape_xml(&absolutize_links(&post.html_content, config))
+            ));
+        }
+        xml.push_str("</entry>\n");
+    }
+
+    xml.push_str("</feed>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::post::PostMetadata;
+
+    fn test_post(slug: &str, date: &str) -> Post {
+        Post {
+            metadata: PostMetadata {
+                title: format!("Post <{}>", slug),
+                date: date.parse().unwrap(),
+                updated: None,
+                author: "Test Author".to_string(),
+                tags: vec!["rust".to_string()],
+                preview: "A & B".to_string(),
+                slug: slug.to_string(),
+                read_time: 1,
+                draft: false,
+                sitemap: true,
+                permalink: None,
+                extra: Default::default(),
+            },
+            url: format!("/posts/{}/", slug),
+            source: Default::default(),
+            content: String::new(),
+            html_content: "<p><img src=\"/posts/a/assets/x.png\"></p>".to_string(),
+            summary: String::new(),
+            toc: Vec::new(),
+            diagrams: Vec::new(),
+        }
+    }
+
+    fn test_config() -> Config {
+        Config {
+            base_url: "https://example.com/".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_escape_xml() {
+        assert_eq!(
+            escape_xml("<a href=\"x\">Tom & Jerry's</a>"),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&apos;s&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn test_rss() {
+        let config = test_config();
+        let posts = vec![test_post("b", "2024-01-02"), test_post("a", "2024-01-01")];
+        let xml = rss(&config, &posts);
+
+        assert!(xml.contains("<link>https://example.com/posts/b/</link>"));
+        assert!(xml.contains("<title>Post &lt;a&gt;</title>"));
+        assert!(xml.contains("<pubDate>Tue, 2 Jan 2024 00:00:00 +0000</pubDate>"));
+        assert!(xml.contains("<description>A &amp; B</description>"));
+        assert!(!xml.contains("x.png"));
+        assert!(xml.find("/posts/b/") < xml.find("/posts/a/"));
+    }
+
+    #[test]
+    fn test_summary_without_preview() {
+        let config = test_config();
+        let mut post = test_post("a", "2024-01-01");
+        post.metadata.preview = String::new();
+        post.summary = "<p>Intro <a href=\"/about/\">me</a></p>".to_string();
+        let posts = vec![post];
+
+        assert!(rss(&config, &posts).contains(
+            "<description>&lt;p&gt;Intro &lt;a href=&quot;https://example.com/about/&quot;&gt;"
+        ));
+        assert!(atom(&config, &posts).contains("<summary type=\"html\">&lt;p&gt;Intro"));
+    }
+
+    #[test]
+    fn test_drafts_excluded() {
+        let config = test_config();
+        let mut draft = test_post("b", "2024-01-02");
+        draft.metadata.draft = true;
+        let posts = vec![draft, test_post("a", "2024-01-01")];
+
+        for xml in [rss(&config, &posts), atom(&config, &posts)] {
+            assert!(!xml.contains("/posts/b/"));
+            assert!(xml.contains("/posts/a/"));
+        }
+    }
+
+    #[test]
+    fn test_atom_full_content_and_limit() {
+        let mut config = test_config();
+        config.feed.full_content = true;
+        config.feed.limit = 1;
+        let posts = vec![test_post("b", "2024-01-02"), test_post("a", "2024-01-01")];
+        let xml = atom(&config, &posts);
+
+        assert!(xml.contains("<updated>2024-01-02T00:00:00+00:00</updated>"));
+        assert!(xml.contains("<id>https://example.com/posts/b/</id>"));
+        assert!(xml.contains("src=&quot;https://example.com/posts/a/assets/x.png&quot;"));
+        assert!(!xml.contains("<id>https://example.com/posts/a/</id>"));
+    }
+}
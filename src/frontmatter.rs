@@ -0,0 +1,401 @@
+use serde::de::{self, DeserializeOwned, Deserializer, Visitor};
+use serde::forward_to_deserialize_any;
+use std::cell::Cell;
+
+use crate::errors::Error;
+
+// Posts and pages open with YAML between `---` lines or TOML between `+++` lines
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    fn delimiter(self) -> &'static str {
+        match self {
+            Format::Yaml => "---",
+            Format::Toml => "+++",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Document<T> {
+    pub metadata: T,
+    // Everything after the closing delimiter
+    pub content: String,
+    // Line of the file `content` starts on, for errors raised while rendering it
+    pub content_line: usize,
+}
+
+// Split off and deserialize a file's frontmatter. Every problem found in it
+// (unknown keys, wrong types, bad dates) is reported, each with its line.
+pub fn parse<T: DeserializeOwned>(file: &str, raw: &str) -> Result<Document<T>, Error> {
+    let error = |line: usize, message: String| Error::Frontmatter {
+        file: file.to_string(),
+        line,
+        message,
+    };
+
+    let first = raw.lines().next().unwrap_or("").trim_end();
+    let format = match first {
+        "---" => Format::Yaml,
+        "+++" => Format::Toml,
+        _ => {
+            return Err(error(
+                1,
+                "missing frontmatter, start the file with `---` (YAML) or `+++` (TOML)".to_string(),
+            ))
+        }
+    };
+
+    // Byte offsets of the frontmatter text and of the line closing it
+    let start = raw.find('\n').map_or(raw.len(), |i| i + 1);
+    let mut offset = start;
+    let mut closing = None;
+    for line in raw[start..].split_inclusive('\n') {
+        if line.trim_end() == format.delimiter() {
+            closing = Some((offset, offset + line.len()));
+            break;
+        }
+        offset += line.len();
+    }
+    let Some((end, body_start)) = closing else {
+        return Err(error(
+            1,
+            format!("frontmatter is never closed with `{}`", format.delimiter()),
+        ));
+    };
+
+    let text = &raw[start..end];
+    let line_at = |offset: usize| FIRST_LINE + text[..offset.min(text.len())].matches('\n').count();
+    let keys = match format {
+        Format::Yaml => yaml_keys(text),
+        Format::Toml => toml_keys(text),
+    }
+    .map_err(|(offset, message)| error(line_at(offset), message))?;
+
+    let known = field_names::<T>();
+    let mut errors: Vec<Error> = keys
+        .iter()
+        .filter(|key| !known.contains(&key.as_str()))
+        .map(|key| error(key_line(text, key, format), unknown_key(key, known)))
+        .collect();
+
+    // Deserializing stops at the first bad value, so the key it's under is
+    // blanked out and the rest tried again, until it deserializes or an error
+    // can't be put down to one key. Blanking keeps line numbers as they were.
+    let mut remaining = text.to_string();
+    let mut blanked: Vec<&str> = Vec::new();
+    let metadata = loop {
+        let (offset, message) = match deserialize::<T>(&remaining, format) {
+            Ok(metadata) => break Some(metadata),
+            Err(e) => e,
+        };
+        let line = FIRST_LINE
+            + remaining[..offset.unwrap_or(0).min(remaining.len())]
+                .matches('\n')
+                .count();
+        // Missing fields come last and belong to no key. One that's only
+        // missing because its bad value was taken out is reported already.
+        if message.starts_with("missing field") || offset.is_none() {
+            if !blanked
+                .iter()
+                .any(|key| message == format!("missing field `{}`", key))
+            {
+                errors.push(error(line, message));
+            }
+            break None;
+        }
+        // Errors name the key they're about, e.g. "tags[1]: invalid type: ..."
+        let key = keys.iter().find(|key| {
+            message
+                .strip_prefix(key.as_str())
+                .is_some_and(|rest| rest.starts_with([':', '[', '.']))
+        });
+        errors.push(error(line, message));
+        let Some(key) = key.filter(|key| !blanked.contains(&key.as_str())) else {
+            break None;
+        };
+        // Through to the next key, so values spanning several lines go too
+        let from = key_line(text, key, format);
+        let to = keys
+            .iter()
+            .map(|key| key_line(text, key, format))
+            .filter(|&other| other > from)
+            .min()
+            .unwrap_or(usize::MAX);
+        remaining = remaining
+            .split_inclusive('\n')
+            .enumerate()
+            .map(|(i, line)| {
+                if (from..to).contains(&(FIRST_LINE + i)) {
+                    if line.ends_with('\n') {
+                        "\n"
+                    } else {
+                        ""
+                    }
+                } else {
+                    line
+                }
+            })
+            .collect();
+        blanked.push(key);
+    };
+
+    match metadata {
+        Some(metadata) if errors.is_empty() => Ok(Document {
+            metadata,
+            content: raw[body_start..].to_string(),
+            content_line: FIRST_LINE + raw[start..body_start].matches('\n').count(),
+        }),
+        _ => {
+            errors.sort_by_key(|e| match e {
+                Error::Frontmatter { line, .. } => *line,
+                _ => 0,
+            });
+            Err(Error::many(errors))
+        }
+    }
+}
+
+// The frontmatter text starts on the line after the opening delimiter
+const FIRST_LINE: usize = 2;
+
+// Offset (if known) and message of the first error deserializing `text`
+fn deserialize<T: DeserializeOwned>(
+    text: &str,
+    format: Format,
+) -> Result<T, (Option<usize>, String)> {
+    match format {
+        Format::Yaml => serde_yaml_ng::from_str(text).map_err(|e| {
+            let message = e.to_string();
+            // The location is reported as a line number instead
+            let message = match message.rfind(" at line ") {
+                Some(i) if e.location().is_some() => message[..i].to_string(),
+                _ => message,
+            };
+            (e.location().map(|l| l.index()), message)
+        }),
+        Format::Toml => toml::from_str(text).map_err(|e| {
+            let (offset, message) = toml_error(text, &e);
+            (e.span().map(|_| offset), message)
+        }),
+    }
+}
+
+fn yaml_keys(text: &str) -> Result<Vec<String>, (usize, String)> {
+    let value: serde_yaml_ng::Value = serde_yaml_ng::from_str(text).map_err(|e| {
+        let message = e.to_string();
+        let message = match message.rfind(" at line ") {
+            Some(i) => message[..i].to_string(),
+            None => message,
+        };
+        (e.location().map_or(0, |l| l.index()), message)
+    })?;
+
+    Ok(match value {
+        serde_yaml_ng::Value::Mapping(mapping) => mapping
+            .iter()
+            .filter_map(|(key, _)| key.as_str().map(str::to_string))
+            .collect(),
+        _ => Vec::new(),
+    })
+}
+
+fn toml_keys(text: &str) -> Result<Vec<String>, (usize, String)> {
+    let table: toml::Table = text.parse().map_err(|e| toml_error(text, &e))?;
+    Ok(table.keys().cloned().collect())
+}
+
+// Offset and message of a TOML error, naming the key like YAML errors do
+// ("tags: invalid type: ...")
+fn toml_error(text: &str, e: &toml::de::Error) -> (usize, String) {
+    let offset = e.span().map_or(0, |span| span.start).min(text.len());
+    let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line = text[line_start..].lines().next().unwrap_or("");
+    let message = e.message().trim().replace('\n', ", ");
+    match line.split_once('=') {
+        Some((key, _)) if !message.starts_with("missing field") => {
+            (offset, format!("{}: {}", key.trim(), message))
+        }
+        _ => (offset, message),
+    }
+}
+
+// Line a top-level key is set on, e.g. `tgas:`, `tgas =` or `[tgas]`
+fn key_line(text: &str, key: &str, format: Format) -> usize {
+    let sets_key = |line: &str| match format {
+        Format::Yaml => line
+            .strip_prefix(key)
+            .is_some_and(|rest| rest.trim_start().starts_with(':')),
+        Format::Toml => {
+            let line = line.trim_start();
+            line.strip_prefix(key)
+                .is_some_and(|rest| rest.trim_start().starts_with('='))
+                || line
+                    .strip_prefix('[')
+                    .and_then(|rest| rest.strip_prefix(key))
+                    .is_some_and(|rest| rest.starts_with([']', '.']))
+        }
+    };
+    FIRST_LINE + text.lines().position(sets_key).unwrap_or(0)
+}
+
+fn unknown_key(key: &str, known: &[&str]) -> String {
+    let closest = known
+        .iter()
+        .map(|candidate| (strsim::damerau_levenshtein(key, candidate), candidate))
+        .min();
+    match closest {
+        Some((distance, candidate)) if distance <= 2 && distance < key.len() => {
+            format!("unknown key `{}`, did you mean `{}`?", key, candidate)
+        }
+        _ if known.contains(&"extra") => format!(
+            "unknown key `{}`, custom values belong under `extra` (expected one of {})",
+            key,
+            list_keys(known)
+        ),
+        _ => format!(
+            "unknown key `{}`, expected one of {}",
+            key,
+            list_keys(known)
+        ),
+    }
+}
+
+fn list_keys(keys: &[&str]) -> String {
+    keys.iter()
+        .map(|k| format!("`{}`", k))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Field names of a metadata struct, as declared to serde by its Deserialize impl
+fn field_names<T: DeserializeOwned>() -> &'static [&'static str] {
+    let fields = Cell::new(&[][..]);
+    let _ = T::deserialize(FieldNames(&fields));
+    fields.get()
+}
+
+// A deserializer that only records the fields it's asked for
+struct FieldNames<'a>(&'a Cell<&'static [&'static str]>);
+
+impl<'de> Deserializer<'de> for FieldNames<'_> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("not a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.0.set(fields);
+        Err(de::Error::custom("fields recorded"))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::post::PostMetadata;
+
+    #[test]
+    fn test_parse_yaml_and_toml() {
+        let yaml = "---\ntitle: \"Hi\"\ndate: 2024-01-01\nslug: hi\n---\n\nBody\n";
+        let doc: Document<PostMetadata> = parse("post.md", yaml).unwrap();
+        assert_eq!(doc.metadata.title, "Hi");
+        assert_eq!(doc.content, "\nBody\n");
+        assert_eq!(doc.content_line, 6);
+
+        let toml =
+            "+++\ntitle = \"Hi\"\ndate = 2024-01-01\nslug = \"hi\"\ntags = [\"rust\"]\n+++\nBody\n";
+        let doc: Document<PostMetadata> = parse("post.md", toml).unwrap();
+        assert_eq!(doc.metadata.date.to_string(), "2024-01-01");
+        assert_eq!(doc.metadata.tags, vec!["rust"]);
+        assert_eq!(doc.content, "Body\n");
+        assert_eq!(doc.content_line, 7);
+    }
+
+    #[test]
+    fn test_unknown_keys_and_type_errors() {
+        let yaml =
+            "---\ntitle: \"Hi\"\ndate: 2024-01-01\ntgas: [rust]\nslug: hi\ndraft: maybe\n---\n";
+        let err = parse::<PostMetadata>("post.md", yaml).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "2 errors:\n\
+             Frontmatter error in post.md:4: unknown key `tgas`, did you mean `tags`?\n\
+             Frontmatter error in post.md:6: draft: invalid type: string \"maybe\", expected a boolean"
+        );
+
+        let toml =
+            "+++\ntitle = \"Hi\"\ndate = \"2024/01/01\"\nslug = \"hi\"\ncolour = \"red\"\n+++\n";
+        let err = parse::<PostMetadata>("post.md", toml).unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("post.md:3: date: invalid date `2024/01/01`"));
+        assert!(message.contains(
+            "post.md:5: unknown key `colour`, custom values belong under `extra` (expected one of `title`"
+        ));
+    }
+
+    #[test]
+    fn test_every_type_error_is_reported() {
+        let yaml = "---\ntitle: [\"Hi\"]\ndate: 2024-01-01\nslug: hi\ntags:\n  - rust\n  - [nested]\ndraft: maybe\n---\n";
+        let err = parse::<PostMetadata>("post.md", yaml).unwrap_err();
+        let message = err.to_string();
+        assert!(message.starts_with("3 errors:\n"));
+        assert!(message.contains("post.md:2: title: invalid type: sequence"));
+        assert!(message.contains("post.md:7: tags[1]: invalid type: sequence"));
+        assert!(message.contains("post.md:8: draft: invalid type: string \"maybe\""));
+        assert!(!message.contains("missing field"));
+
+        let toml = "+++\ntitle = 1\ndate = 2024-01-01\nslug = \"hi\"\ndraft = \"maybe\"\n+++\n";
+        let err = parse::<PostMetadata>("post.md", toml).unwrap_err();
+        let message = err.to_string();
+        assert!(message.starts_with("2 errors:\n"));
+        assert!(message.contains("post.md:2: title"));
+        assert!(message.contains("post.md:5: draft"));
+
+        // Still reported alongside the values that are wrong
+        let yaml = "---\ntitle: Hi\ndate: 2024-01-01\ndraft: maybe\n---\n";
+        let err = parse::<PostMetadata>("post.md", yaml).unwrap_err();
+        assert!(err.to_string().contains("missing field `slug`"));
+    }
+
+    #[test]
+    fn test_extra_values() {
+        let yaml = "---\ntitle: Hi\ndate: 2024-01-01\nslug: hi\nextra:\n  cover: hero.png\n  series: { name: Rust, part: 2 }\n---\n";
+        let doc: Document<PostMetadata> = parse("post.md", yaml).unwrap();
+        assert_eq!(doc.metadata.extra["cover"], "hero.png");
+        assert_eq!(doc.metadata.extra["series"]["part"], 2);
+
+        let toml = "+++\ntitle = \"Hi\"\ndate = 2024-01-01\nslug = \"hi\"\n\n[extra]\ncover = \"hero.png\"\nfeatured = true\n+++\n";
+        let doc: Document<PostMetadata> = parse("post.md", toml).unwrap();
+        assert_eq!(doc.metadata.extra["cover"], "hero.png");
+        assert_eq!(doc.metadata.extra["featured"], true);
+    }
+
+    #[test]
+    fn test_missing_or_unclosed_frontmatter() {
+        let err = parse::<PostMetadata>("post.md", "# Hi\n").unwrap_err();
+        assert!(err.to_string().contains("post.md:1: missing frontmatter"));
+
+        let err = parse::<PostMetadata>("post.md", "+++\ntitle = \"Hi\"\n").unwrap_err();
+        assert!(err.to_string().contains("never closed with `+++`"));
+
+        let err = parse::<PostMetadata>("post.md", "---\ntitle: [\n---\n").unwrap_err();
+        assert!(err.to_string().starts_with("Frontmatter error in post.md:"));
+    }
+}
@@ -1,18 +1,35 @@
-use chrono::NaiveDate;
+use chrono::Utc;
+use chrono_tz::Tz;
 use console::Style;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
 use std::fs::{self};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
 use tera::{Context, Tera};
 use walkdir::WalkDir;
 
+use crate::cache::{hash_bytes, BuildCache};
 use crate::config::Config;
+use crate::date::{date_filter, PostDate};
 use crate::errors::Error;
-use crate::markdown::MarkdownProcessor;
-use crate::post::Post;
+use crate::feed;
+use crate::git;
+use crate::markdown::{self, MarkdownProcessor};
+use crate::page::Page;
+use crate::paginator::{page_url, paginate};
+use crate::post::{slugify, Post};
+use crate::sitemap::{self, SitemapEntry};
+use crate::tags::collect_tags;
 
 pub struct SiteGenerator {
     config: Config,
     tera: Tera,
     markdown: MarkdownProcessor,
+    cache: Option<Mutex<BuildCache>>,
+    pool: rayon::ThreadPool,
+    timezone: Tz,
 }
 
 impl SiteGenerator {
@@ -38,32 +55,296 @@ impl SiteGenerator {
         }
 
         let template_pattern = format!("{}/**/*.html", templates_dir.display());
-        let tera = Tera::new(&template_pattern).map_err(Error::Template)?;
+        let mut tera = Tera::new(&template_pattern).map_err(Error::Template)?;
+
+        // Shadow Tera's builtin so template-built URLs match the slugs we write
+        tera.register_filter(
+            "slugify",
+            |value: &tera::Value, _: &std::collections::HashMap<String, tera::Value>| {
+                let text = tera::from_value::<String>(value.clone())?;
+                Ok(tera::to_value(slugify(&text))?)
+            },
+        );
+        let timezone = config.timezone()?;
+        tera.register_filter("date", date_filter(timezone));
+
+        let cache = if config.build.cache {
+            Some(Mutex::new(BuildCache::load(config)?))
+        } else {
+            // This build changes the output without recording it, so nothing
+            // cached so far can be trusted afterwards
+            BuildCache::clear(config)?;
+            None
+        };
+
+        // 0 lets rayon pick one thread per core
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(config.build.jobs.unwrap_or(0))
+            .build()
+            .map_err(|e| Error::Other(Box::new(e)))?;
 
         Ok(Self {
             config: config.clone(),
             tera,
-            markdown: MarkdownProcessor::new(),
+            markdown: MarkdownProcessor::from_config(config)?,
+            cache,
+            pool,
+            timezone,
         })
     }
 
     fn read_posts(&self) -> Result<Vec<Post>, Error> {
         let mut posts = Vec::new();
+        let mut scheduled = Vec::new();
         let posts_dir = self.config.posts_dir();
+        let now = Utc::now();
 
-        for entry in WalkDir::new(posts_dir)
+        let paths: Vec<PathBuf> = WalkDir::new(posts_dir)
             .min_depth(1)
             .max_depth(2)
+            .sort_by_file_name()
             .into_iter()
             .filter_map(|e| e.ok())
-            .filter(|e| e.path().extension().map_or(false, |ext| ext == "md"))
-        {
-            posts.push(Post::new_from_path(entry.path(), &self.markdown)?);
+            .filter(|e| e.path().extension().is_some_and(|ext| ext == "md"))
+            .map(|e| e.into_path())
+            .collect();
+
+        // Markdown rendering and highlighting dominate build time, so parse
+        // posts across the pool; collecting keeps them in path order
+        let parsed = self.pool.install(|| {
+            paths
+                .par_iter()
+                .map(|path| self.read_post(path))
+                .collect::<Vec<_>>()
+        });
+        let mut parsed = all_or_errors(&paths, parsed)?;
+        // Drafts and scheduled posts too, as later builds may include them
+        self.use_diagrams(parsed.iter().flat_map(|post| &post.diagrams));
+
+        if self.config.build.updated_from_git {
+            let times = git::last_commit_times(&paths)?;
+            for (post, path) in parsed.iter_mut().zip(&paths) {
+                let Some(time) = times.get(path) else {
+                    continue;
+                };
+                let updated = PostDate::from_datetime(time.with_timezone(&self.timezone));
+                // The commit that published a post doesn't make it updated
+                if post.metadata.updated.is_none()
+                    && updated.date_naive() > post.metadata.date.date_naive()
+                {
+                    post.metadata.updated = Some(updated);
+                }
+            }
+        }
+
+        for post in parsed {
+            if post.metadata.draft && !self.config.build.drafts {
+                if self.config.build.verbose {
+                    println!("Skipping draft: {}", post.metadata.title);
+                }
+                continue;
+            }
+
+            if !self.config.build.future && post.metadata.date.datetime() > now {
+                scheduled.push((post.metadata.date, post.metadata.title));
+                continue;
+            }
+
+            posts.push(post);
+        }
+
+        scheduled.sort();
+        if !scheduled.is_empty() && self.announce_scheduled(&scheduled) {
+            println!(
+                "{}",
+                Style::new().yellow().apply_to(format!(
+                    "⏳ Holding back {} scheduled post(s), rebuild once they are due or pass --future:",
+                    scheduled.len()
+                ))
+            );
+            for (date, title) in &scheduled {
+                println!("  - {} (goes live {})", title, date);
+            }
         }
 
         Ok(posts)
     }
 
+    // Parse and render a post, or take the rendering an earlier build cached
+    // for the same source so only new and edited posts are rendered
+    fn read_post(&self, path: &Path) -> Result<Post, Error> {
+        let render =
+            || Post::new_from_path(path, &self.markdown, self.timezone, &self.config.permalink);
+        let Some(cache) = &self.cache else {
+            return render();
+        };
+
+        let rendered_path = cache
+            .lock()
+            .unwrap()
+            .rendered_path(&self.config, &source_hash(path)?);
+        let cached = fs::read_to_string(&rendered_path)
+            .ok()
+            .and_then(|json| json.parse::<tera::Value>().ok())
+            .and_then(|value| Post::from_cache(value, path, self.timezone).ok());
+        if let Some(post) = cached {
+            return Ok(post);
+        }
+
+        let post = render()?;
+        if let Some(parent) = rendered_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let value = tera::to_value(&post).map_err(tera::Error::from)?;
+        fs::write(&rendered_path, value.to_string())?;
+        Ok(post)
+    }
+
+    // `serve` rebuilds on every change, so the held back posts are only listed
+    // again when they differ from what this process last reported (or with -v)
+    fn announce_scheduled(&self, scheduled: &[(PostDate, String)]) -> bool {
+        static ANNOUNCED: Mutex<Vec<(PostDate, String)>> = Mutex::new(Vec::new());
+
+        let mut announced = ANNOUNCED.lock().unwrap();
+        if self.config.build.verbose || *announced != scheduled {
+            *announced = scheduled.to_vec();
+            true
+        } else {
+            false
+        }
+    }
+
+    // Record an output of a draft or scheduled post (built with `--drafts` or
+    // `--future`), so a later build without the cache knows to take it down.
+    // Cached builds prune it like any output they no longer write.
+    fn note_unpublished(&self, post: &Post, output_path: &Path) {
+        if !post.metadata.draft && post.metadata.date.datetime() <= Utc::now() {
+            return;
+        }
+        if let Some(cache) = &self.cache {
+            cache.lock().unwrap().draft(self.cache_key(output_path));
+        }
+    }
+
+    fn read_pages(&self) -> Result<Vec<Page>, Error> {
+        let pages_dir = self.config.pages_dir();
+        if !pages_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let paths: Vec<PathBuf> = WalkDir::new(&pages_dir)
+            .min_depth(1)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().extension().is_some_and(|ext| ext == "md"))
+            .map(|e| e.into_path())
+            .collect();
+
+        let parsed = self.pool.install(|| {
+            paths
+                .par_iter()
+                .map(|path| Page::new_from_path(path, &pages_dir, &self.markdown))
+                .collect()
+        });
+        let pages = all_or_errors(&paths, parsed)?;
+        self.use_diagrams(pages.iter().flat_map(|page| &page.diagrams));
+        Ok(pages)
+    }
+
+    fn use_diagrams<'a>(&self, keys: impl IntoIterator<Item = &'a String>) {
+        if let Some(cache) = &self.cache {
+            cache.lock().unwrap().use_diagrams(keys);
+        }
+    }
+
+    fn generate_pages(&self, pages: &[Page]) -> Result<(), Error> {
+        let results = self.pool.install(|| {
+            pages
+                .par_iter()
+                .map(|page| self.generate_page(page))
+                .collect()
+        });
+        let sources: Vec<PathBuf> = pages.iter().map(|page| page.source.clone()).collect();
+        all_or_errors(&sources, results).map(|_| ())
+    }
+
+    fn generate_page(&self, page: &Page) -> Result<(), Error> {
+        let mut context = Context::new();
+        context.insert("page", page);
+        context.insert("config", &self.config);
+        context.insert("title", &page.metadata.title);
+
+        let html = self.tera.render("page.html", &context)?;
+
+        self.write_output(&self.output_path_for_url(&page.url)?, html)?;
+
+        if self.config.build.verbose {
+            println!("Generated page: {}", page.url);
+        }
+        Ok(())
+    }
+
+    fn cache_key(&self, output_path: &Path) -> String {
+        output_path
+            .strip_prefix(self.config.output_dir())
+            .unwrap_or(output_path)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    // Check the build cache for an output produced from inputs hashing to `hash`
+    fn is_cached(&self, output_path: &Path, hash: &str) -> bool {
+        self.cache.as_ref().is_some_and(|cache| {
+            cache
+                .lock()
+                .unwrap()
+                .is_fresh(&self.cache_key(output_path), hash, output_path)
+        })
+    }
+
+    fn update_cache(&self, output_path: &Path, hash: String) {
+        if let Some(cache) = &self.cache {
+            cache
+                .lock()
+                .unwrap()
+                .update(self.cache_key(output_path), hash);
+        }
+    }
+
+    // Write a generated file, noting it as an output of this build so files
+    // earlier builds wrote but this one didn't can be cleaned up
+    fn write_output(&self, path: &Path, contents: impl AsRef<[u8]>) -> Result<(), Error> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, contents)?;
+        if let Some(cache) = &self.cache {
+            cache.lock().unwrap().produced(self.cache_key(path));
+        }
+        Ok(())
+    }
+
+    // Copy a file into the output directory unless an identical copy from the
+    // last build is still there. Returns whether the file was copied.
+    fn copy_file(&self, src: &Path, dest: &Path) -> Result<bool, Error> {
+        let hash = match &self.cache {
+            Some(_) => hash_bytes(&fs::read(src)?),
+            None => String::new(),
+        };
+        if self.is_cached(dest, &hash) {
+            return Ok(false);
+        }
+
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(src, dest)?;
+
+        self.update_cache(dest, hash);
+        Ok(true)
+    }
+
     fn copy_post_assets(&self, post: &Post) -> Result<(), Error> {
         let assets_dir = post.assets_dir(&self.config);
 
@@ -90,14 +371,9 @@ impl SiteGenerator {
                 .map_err(|_| Error::DirectoryNotFound(assets_dir.clone()))?;
 
             let dest_path = output_dir.join(rel_path);
+            self.note_unpublished(post, &dest_path);
 
-            if let Some(parent) = dest_path.parent() {
-                fs::create_dir_all(parent)?;
-            }
-
-            fs::copy(entry.path(), &dest_path)?;
-
-            if self.config.build.verbose {
+            if self.copy_file(entry.path(), &dest_path)? && self.config.build.verbose {
                 println!("    Copied: {}", rel_path.display());
             }
         }
@@ -112,6 +388,23 @@ impl SiteGenerator {
         // Copy post assets
         self.copy_post_assets(post)?;
 
+        let output_path = self.output_path_for_url(&post.url)?;
+        self.note_unpublished(post, &output_path);
+
+        // Config and templates are covered by the cache fingerprint, so the
+        // page only needs re-rendering when the post's source (or its date
+        // from git) changed
+        let hash = match &self.cache {
+            Some(_) => {
+                let updated = post.metadata.updated.map(|date| date.to_string());
+                hash_bytes(format!("{}{:?}", source_hash(&post.source)?, updated).as_bytes())
+            }
+            None => String::new(),
+        };
+        if self.is_cached(&output_path, &hash) {
+            return Ok(());
+        }
+
         let mut context = Context::new();
         context.insert("post", post);
         context.insert("config", &self.config);
@@ -119,29 +412,288 @@ impl SiteGenerator {
 
         let html = self.tera.render("post.html", &context)?;
 
-        let output_path = self
-            .config
-            .output_dir()
-            .join("posts")
-            .join(&post.metadata.slug)
-            .join("index.html");
+        self.write_output(&output_path, html)?;
+        self.update_cache(&output_path, hash);
+        Ok(())
+    }
 
-        if let Some(parent) = output_path.parent() {
-            fs::create_dir_all(parent)?;
+    // Map a site URL such as "/tags/rust/page/2/" to its index.html in the output directory
+    fn output_path_for_url(&self, url: &str) -> Result<PathBuf, Error> {
+        let relative = Path::new(url.trim_matches('/'));
+        // Never write outside the output directory, whatever a URL contains
+        if !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            return Err(Error::Permalink(format!(
+                "{} points outside the output directory",
+                url
+            )));
+        }
+        Ok(self.config.output_dir().join(relative).join("index.html"))
+    }
+
+    // Check that no two things are published at the same URL: posts and pages
+    // can't replace each other or the index, tag pages, feeds and sitemap
+    fn check_urls(&self, posts: &[Post], pages: &[Page]) -> Result<(), Error> {
+        let per_page = self.config.build.paginate_by;
+        let mut claims: Vec<(String, String)> = paginate(posts, per_page, "/")
+            .iter()
+            .map(|page| {
+                (
+                    page_url("/", page.paginator.current_page),
+                    "the index".to_string(),
+                )
+            })
+            .collect();
+
+        if self.has_template("tag.html") && self.has_template("tags.html") {
+            claims.push(("/tags/".to_string(), "the tag index".to_string()));
+            for (tag, tagged_posts) in collect_tags(posts) {
+                let url_base = format!("/tags/{}/", tag.slug);
+                for page in paginate(&tagged_posts, per_page, &url_base) {
+                    claims.push((
+                        page_url(&url_base, page.paginator.current_page),
+                        format!("the `{}` tag page", tag.name),
+                    ));
+                }
+            }
+        }
+
+        let mut files = vec!["/sitemap.xml", "/robots.txt"];
+        if self.config.feed.enabled {
+            files.extend(["/rss.xml", "/atom.xml"]);
         }
+        claims.extend(
+            files
+                .into_iter()
+                .map(|file| (file.to_string(), file.to_string())),
+        );
 
-        fs::write(output_path, html)?;
+        claims.extend(
+            posts
+                .iter()
+                .map(|post| (post.url.clone(), post.source.display().to_string())),
+        );
+        claims.extend(
+            pages
+                .iter()
+                .map(|page| (page.url.clone(), page.source.display().to_string())),
+        );
+
+        let mut owners: HashMap<String, String> = HashMap::new();
+        let mut collisions = Vec::new();
+        for (url, owner) in claims {
+            // "/rss.xml/" would be written as rss.xml/index.html, where the feed is
+            match owners.get(url.trim_matches('/')) {
+                Some(first) => collisions.push(Error::Permalink(format!(
+                    "{} and {} are both published at {}",
+                    first, owner, url
+                ))),
+                None => {
+                    owners.insert(url.trim_matches('/').to_string(), owner);
+                }
+            }
+        }
+
+        if collisions.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::many(collisions))
+        }
+    }
+
+    // Render a post listing rooted at `url_base`, split into pages of
+    // `build.paginate_by` posts. Each page gets `posts` and `paginator` added
+    // on top of the given context.
+    fn render_listing<T: Serialize>(
+        &self,
+        template: &str,
+        url_base: &str,
+        posts: &[T],
+        context: &Context,
+    ) -> Result<(), Error> {
+        for page in paginate(posts, self.config.build.paginate_by, url_base) {
+            let mut context = context.clone();
+            context.insert("posts", page.items);
+            context.insert("paginator", &page.paginator);
+
+            let html = self.tera.render(template, &context)?;
+
+            let output_path =
+                self.output_path_for_url(&page_url(url_base, page.paginator.current_page))?;
+            self.write_output(&output_path, html)?;
+        }
         Ok(())
     }
 
     fn generate_index_page(&self, posts: &[Post]) -> Result<(), Error> {
         let mut context = Context::new();
-        context.insert("posts", posts);
         context.insert("config", &self.config);
         context.insert("title", &self.config.title);
 
-        let html = self.tera.render("index.html", &context)?;
-        fs::write(self.config.output_dir().join("index.html"), html)?;
+        self.render_listing("index.html", "/", posts, &context)
+    }
+
+    fn has_template(&self, name: &str) -> bool {
+        self.tera.get_template_names().any(|t| t == name)
+    }
+
+    fn generate_tag_pages(&self, posts: &[Post]) -> Result<(), Error> {
+        // Sites scaffolded before tag support won't have these templates
+        if !self.has_template("tag.html") || !self.has_template("tags.html") {
+            if self.config.build.verbose {
+                println!("Skipping tag pages: tag.html or tags.html template not found");
+            }
+            return Ok(());
+        }
+
+        let tags = collect_tags(posts);
+
+        for (tag, tagged_posts) in &tags {
+            let mut context = Context::new();
+            context.insert("tag", tag);
+            context.insert("config", &self.config);
+            context.insert("title", &format!("Posts tagged \"{}\"", tag.name));
+
+            self.render_listing(
+                "tag.html",
+                &format!("/tags/{}/", tag.slug),
+                tagged_posts,
+                &context,
+            )?;
+
+            if self.config.build.verbose {
+                println!("Generated tag page: {} ({} posts)", tag.name, tag.count);
+            }
+        }
+
+        let summaries: Vec<_> = tags.iter().map(|(tag, _)| tag).collect();
+
+        let mut context = Context::new();
+        context.insert("tags", &summaries);
+        context.insert("config", &self.config);
+        context.insert("title", "Tags");
+
+        let html = self.tera.render("tags.html", &context)?;
+        self.write_output(&self.output_path_for_url("/tags/")?, html)?;
+        Ok(())
+    }
+
+    fn generate_feeds(&self, posts: &[Post]) -> Result<(), Error> {
+        if !self.config.feed.enabled {
+            return Ok(());
+        }
+
+        let output_dir = self.config.output_dir();
+        self.write_output(&output_dir.join("rss.xml"), feed::rss(&self.config, posts))?;
+        self.write_output(
+            &output_dir.join("atom.xml"),
+            feed::atom(&self.config, posts),
+        )?;
+
+        if self.config.build.verbose {
+            println!("Generated RSS and Atom feeds");
+        }
+        Ok(())
+    }
+
+    fn generate_syntax_css(&self) -> Result<(), Error> {
+        // A hand-written stylesheet in static/ takes precedence
+        if self.config.static_dir().join("css/syntax.css").exists() {
+            return Ok(());
+        }
+        let Some(css) = markdown::syntax_css(&self.config)? else {
+            return Ok(());
+        };
+
+        let css_path = self.config.output_dir().join("css/syntax.css");
+        self.write_output(&css_path, css)?;
+
+        if self.config.build.verbose {
+            println!("Generated syntax highlighting CSS");
+        }
+        Ok(())
+    }
+
+    // Sitemap entries for every page of a listing, dated by its latest change.
+    // `posts` is split into pages as `render_listing` splits it, so drafts
+    // built with --drafts take up room but don't date the pages.
+    fn listing_sitemap_entries(&self, url_base: &str, posts: &[&Post]) -> Vec<SitemapEntry> {
+        paginate(posts, self.config.build.paginate_by, url_base)
+            .into_iter()
+            .map(|page| {
+                SitemapEntry::new(
+                    page_url(url_base, page.paginator.current_page),
+                    page.items
+                        .iter()
+                        .filter(|post| !post.metadata.draft)
+                        .map(|post| post.last_modified())
+                        .max()
+                        .map(|date| date.to_string()),
+                )
+            })
+            .collect()
+    }
+
+    fn generate_sitemap(&self, posts: &[Post], pages: &[Page]) -> Result<(), Error> {
+        let all: Vec<&Post> = posts.iter().collect();
+        // Drafts built with --drafts are never advertised themselves
+        let published: Vec<&Post> = posts.iter().filter(|post| !post.metadata.draft).collect();
+        let mut entries = self.listing_sitemap_entries("/", &all);
+
+        entries.extend(
+            published
+                .iter()
+                .filter(|post| post.metadata.sitemap)
+                .map(|post| {
+                    SitemapEntry::new(post.url.clone(), Some(post.last_modified().to_string()))
+                }),
+        );
+
+        entries.extend(
+            pages
+                .iter()
+                .filter(|page| page.metadata.sitemap)
+                .map(|page| SitemapEntry::new(page.url.clone(), None)),
+        );
+
+        if self.has_template("tag.html") && self.has_template("tags.html") {
+            entries.push(SitemapEntry::new(
+                "/tags/",
+                published
+                    .iter()
+                    .map(|post| post.last_modified())
+                    .max()
+                    .map(|date| date.to_string()),
+            ));
+            for (tag, tagged_posts) in collect_tags(posts) {
+                // Nor are tags only drafts use
+                if tagged_posts.iter().all(|post| post.metadata.draft) {
+                    continue;
+                }
+                entries.extend(
+                    self.listing_sitemap_entries(&format!("/tags/{}/", tag.slug), &tagged_posts),
+                );
+            }
+        }
+
+        let output_dir = self.config.output_dir();
+        self.write_output(
+            &output_dir.join("sitemap.xml"),
+            sitemap::sitemap(&self.config, &entries),
+        )?;
+        // A robots.txt in static/ takes precedence
+        if !self.config.static_dir().join("robots.txt").exists() {
+            self.write_output(
+                &output_dir.join("robots.txt"),
+                sitemap::robots(&self.config),
+            )?;
+        }
+
+        if self.config.build.verbose {
+            println!("Generated sitemap with {} URLs", entries.len());
+        }
         Ok(())
     }
 
@@ -160,13 +712,7 @@ impl SiteGenerator {
 
                 let dest_path = self.config.output_dir().join(relative_path);
 
-                if let Some(parent) = dest_path.parent() {
-                    fs::create_dir_all(parent)?;
-                }
-
-                fs::copy(entry.path(), &dest_path)?;
-
-                if self.config.build.verbose {
+                if self.copy_file(entry.path(), &dest_path)? && self.config.build.verbose {
                     println!("Copied static file: {}", relative_path.display());
                 }
             }
@@ -184,33 +730,77 @@ impl SiteGenerator {
         // Ensure the output directory exists
         fs::create_dir_all(self.config.output_dir())?;
 
+        // Read pages up front too, so one run reports every broken file
         pb.set_message("Reading posts...");
-        let mut posts = self.read_posts()?;
+        let (mut posts, pages) = match (self.read_posts(), self.read_pages()) {
+            (Ok(posts), Ok(pages)) => (posts, pages),
+            (posts, pages) => {
+                return Err(Error::many(
+                    posts.err().into_iter().chain(pages.err()).collect(),
+                ))
+            }
+        };
 
         pb.set_message("Sorting posts...");
         posts.sort_by(|a, b| {
-            let parse_date = |date: &str| NaiveDate::parse_from_str(date, "%Y-%m-%d");
-            match (parse_date(&a.metadata.date), parse_date(&b.metadata.date)) {
-                (Ok(date_a), Ok(date_b)) => date_b.cmp(&date_a),
-                _ => b.metadata.date.cmp(&a.metadata.date),
-            }
+            b.metadata
+                .date
+                .cmp(&a.metadata.date)
+                // Posts published at the same moment fall back to slug order so output is deterministic
+                .then_with(|| a.metadata.slug.cmp(&b.metadata.slug))
         });
+        self.check_urls(&posts, &pages)?;
 
-        pb.set_message("Generating post pages and copying assets...");
-        for post in &mut posts {
-            pb.set_message(format!("Processing post: {}", post.metadata.title));
+        pb.set_message(format!(
+            "Generating {} post pages and copying assets...",
+            posts.len()
+        ));
+        let results = self.pool.install(|| {
+            posts
+                .par_iter_mut()
+                .map(|post| self.generate_post_page(post))
+                .collect()
+        });
+        let sources: Vec<PathBuf> = posts.iter().map(|post| post.source.clone()).collect();
+        all_or_errors(&sources, results)?;
 
-            // First copy assets, then generate the page with updated paths
-            self.copy_post_assets(post)?;
-            self.generate_post_page(post)?;
-        }
+        pb.set_message("Generating pages...");
+        self.generate_pages(&pages)?;
 
         pb.set_message("Generating index page...");
         self.generate_index_page(&posts)?;
 
+        pb.set_message("Generating tag pages...");
+        self.generate_tag_pages(&posts)?;
+
+        pb.set_message("Generating feeds...");
+        self.generate_feeds(&posts)?;
+
+        pb.set_message("Generating sitemap...");
+        self.generate_sitemap(&posts, &pages)?;
+
+        pb.set_message("Generating syntax highlighting CSS...");
+        self.generate_syntax_css()?;
+
         pb.set_message("Copying static assets...");
         self.copy_static_files()?;
 
+        if let Some(cache) = &self.cache {
+            let mut cache = cache.lock().unwrap();
+            let removed = cache.prune(&self.config.output_dir())?;
+            cache.save(&self.config)?;
+
+            if self.config.build.verbose {
+                println!(
+                    "Reused {} unchanged files from the build cache",
+                    cache.reused()
+                );
+                if removed > 0 {
+                    println!("Removed {} files no longer produced by the site", removed);
+                }
+            }
+        }
+
         pb.finish_and_clear();
 
         if self.config.build.verbose {
@@ -222,10 +812,37 @@ impl SiteGenerator {
     }
 }
 
+// Hash of a source file and its location, which the URL can depend on
+fn source_hash(path: &Path) -> Result<String, Error> {
+    let mut source = path.to_string_lossy().into_owned().into_bytes();
+    source.extend(fs::read(path)?);
+    Ok(hash_bytes(&source))
+}
+
+// Every successfully processed file, or every error if any file failed. The
+// errors are listed by path so parallel work can't change their order.
+fn all_or_errors<T>(paths: &[PathBuf], results: Vec<Result<T, Error>>) -> Result<Vec<T>, Error> {
+    let mut ok = Vec::new();
+    let mut errors = Vec::new();
+    for (path, result) in paths.iter().zip(results) {
+        match result {
+            Ok(value) => ok.push(value),
+            Err(error) => errors.push((path, error)),
+        }
+    }
+
+    if errors.is_empty() {
+        return Ok(ok);
+    }
+    errors.sort_by(|a, b| a.0.cmp(b.0));
+    Err(Error::many(errors.into_iter().map(|(_, e)| e).collect()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::cache::{CACHE_DIR, DIAGRAMS_DIR};
     use crate::tests::{create_test_config, setup_test_site};
 
     use std::fs;
@@ -252,7 +869,113 @@ mod tests {
         let posts = generator.read_posts()?;
         assert_eq!(posts.len(), 1);
         assert_eq!(posts[0].metadata.title, "Test Post");
-        assert_eq!(posts[0].metadata.date, "2024-01-01");
+        assert_eq!(posts[0].metadata.date.to_string(), "2024-01-01");
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_posts_drafts() -> Result<(), Error> {
+        let temp_dir = TempDir::new()?;
+        setup_test_site(&temp_dir)?;
+
+        fs::write(
+            temp_dir.path().join("posts/draft-post.md"),
+            r#"---
+title: "Draft Post"
+date: 2024-01-02
+slug: "draft-post"
+tags: ["wip"]
+draft: true
+---
+Work in progress"#,
+        )?;
+
+        let mut config = create_test_config(&temp_dir);
+        let generator = SiteGenerator::new(&config)?;
+        let posts = generator.read_posts()?;
+        assert_eq!(posts.len(), 1);
+        assert_eq!(posts[0].metadata.slug, "test-post");
+
+        config.build.drafts = true;
+        config.build.paginate_by = Some(1);
+        let generator = SiteGenerator::new(&config)?;
+        let posts = generator.read_posts()?;
+        assert_eq!(posts.len(), 2);
+        assert!(posts.iter().any(|p| p.metadata.draft));
+
+        generator.generate_site()?;
+        assert!(config
+            .output_dir()
+            .join("posts/draft-post/index.html")
+            .exists());
+        let sitemap = fs::read_to_string(config.output_dir().join("sitemap.xml"))?;
+        assert!(!sitemap.contains("draft-post"));
+        assert!(!sitemap.contains("/tags/wip/"));
+        // The draft still takes up the first index page
+        assert!(config.output_dir().join("page/2/index.html").exists());
+        assert!(sitemap.contains("<loc>http://localhost:8000/page/2/</loc>"));
+
+        // A normal build afterwards takes the draft back down
+        config.build.drafts = false;
+        SiteGenerator::new(&config)?.generate_site()?;
+        assert!(!config.output_dir().join("posts/draft-post").exists());
+
+        // Even when it runs without the cache
+        config.build.drafts = true;
+        SiteGenerator::new(&config)?.generate_site()?;
+        config.build.drafts = false;
+        config.build.cache = false;
+        SiteGenerator::new(&config)?.generate_site()?;
+        assert!(!config.output_dir().join("posts/draft-post").exists());
+        assert!(config
+            .output_dir()
+            .join("posts/test-post/index.html")
+            .exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_skipped_draft_keeps_published_page_at_its_url() -> Result<(), Error> {
+        let temp_dir = TempDir::new()?;
+        setup_test_site(&temp_dir)?;
+        let config = create_test_config(&temp_dir);
+        SiteGenerator::new(&config)?.generate_site()?;
+
+        // A draft of a rewrite, at the URL of the post it will replace
+        fs::write(
+            temp_dir.path().join("posts/rewrite.md"),
+            "---\ntitle: \"Rewrite\"\ndate: 2024-01-02\nslug: \"test-post\"\ndraft: true\n---\nNew",
+        )?;
+        SiteGenerator::new(&config)?.generate_site()?;
+
+        let page = config.output_dir().join("posts/test-post/index.html");
+        assert!(fs::read_to_string(page)?.contains("Test body"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_posts_future() -> Result<(), Error> {
+        let temp_dir = TempDir::new()?;
+        setup_test_site(&temp_dir)?;
+
+        let tomorrow = Utc::now().date_naive() + chrono::Days::new(1);
+        fs::write(
+            temp_dir.path().join("posts/scheduled-post.md"),
+            format!(
+                "---\ntitle: \"Scheduled Post\"\ndate: {}\nslug: \"scheduled-post\"\n---\nComing soon",
+                tomorrow.format("%Y-%m-%d")
+            ),
+        )?;
+
+        let mut config = create_test_config(&temp_dir);
+        let generator = SiteGenerator::new(&config)?;
+        let posts = generator.read_posts()?;
+        assert_eq!(posts.len(), 1);
+        assert_eq!(posts[0].metadata.slug, "test-post");
+
+        config.build.future = true;
+        let generator = SiteGenerator::new(&config)?;
+        assert_eq!(generator.read_posts()?.len(), 2);
         Ok(())
     }
 
@@ -286,6 +1009,320 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_generate_tag_pages() -> Result<(), Error> {
+        let temp_dir = TempDir::new()?;
+        setup_test_site(&temp_dir)?;
+
+        fs::write(
+            temp_dir.path().join("posts/tagged-post.md"),
+            r#"---
+title: "Tagged Post"
+date: 2024-01-02
+tags: ["test", "Rust Lang"]
+slug: "tagged-post"
+---
+Tagged content"#,
+        )?;
+
+        let config = create_test_config(&temp_dir);
+        let generator = SiteGenerator::new(&config)?;
+
+        generator.generate_site()?;
+
+        let output_dir = config.output_dir();
+        let test_tag = fs::read_to_string(output_dir.join("tags/test/index.html"))?;
+        assert!(test_tag.find("Tagged Post") < test_tag.find("Test Post"));
+
+        let rust_tag = fs::read_to_string(output_dir.join("tags/rust-lang/index.html"))?;
+        assert!(rust_tag.contains("Tagged Post"));
+        assert!(!rust_tag.contains("Test Post"));
+
+        let tag_index = fs::read_to_string(output_dir.join("tags/index.html"))?;
+        assert!(tag_index.contains("Rust Lang:1"));
+        assert!(tag_index.contains("test:2"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_paginated_index_and_tag_pages() -> Result<(), Error> {
+        let temp_dir = TempDir::new()?;
+        setup_test_site(&temp_dir)?;
+
+        for day in 2..=5 {
+            fs::write(
+                temp_dir.path().join(format!("posts/post-{}.md", day)),
+                format!(
+                    "---\ntitle: \"Post {day}\"\ndate: 2024-01-0{day}\ntags: [\"test\"]\nslug: \"post-{day}\"\n---\nBody"
+                ),
+            )?;
+        }
+        fs::write(
+            temp_dir.path().join("templates/index.html"),
+            "{% for post in posts %}{{ post.metadata.title }};{% endfor %}{{ paginator.current_page }}/{{ paginator.total_pages }} prev={{ paginator.previous_url | default(value=\"\") | safe }} next={{ paginator.next_url | default(value=\"\") | safe }}",
+        )?;
+
+        let mut config = create_test_config(&temp_dir);
+        config.build.paginate_by = Some(2);
+        let generator = SiteGenerator::new(&config)?;
+        generator.generate_site()?;
+
+        let output_dir = config.output_dir();
+        let first = fs::read_to_string(output_dir.join("index.html"))?;
+        assert!(first.contains("Post 5;Post 4;1/3"));
+        assert!(first.contains("prev= next=/page/2/"));
+
+        let last = fs::read_to_string(output_dir.join("page/3/index.html"))?;
+        assert!(last.contains("Test Post;3/3"));
+        assert!(last.contains("prev=/page/2/ next="));
+
+        assert!(output_dir.join("tags/test/page/3/index.html").exists());
+        assert!(!output_dir.join("tags/test/page/4/index.html").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_sitemap() -> Result<(), Error> {
+        let temp_dir = TempDir::new()?;
+        setup_test_site(&temp_dir)?;
+
+        fs::write(
+            temp_dir.path().join("posts/hidden-post.md"),
+            r#"---
+title: "Hidden Post"
+date: 2024-01-02
+slug: "hidden-post"
+sitemap: false
+---
+Not for search engines"#,
+        )?;
+
+        let config = create_test_config(&temp_dir);
+        let generator = SiteGenerator::new(&config)?;
+        generator.generate_site()?;
+
+        let sitemap = fs::read_to_string(config.output_dir().join("sitemap.xml"))?;
+        assert!(
+            sitemap.contains("<loc>http://localhost:8000/</loc>\n<lastmod>2024-01-02</lastmod>")
+        );
+        assert!(sitemap.contains(
+            "<loc>http://localhost:8000/posts/test-post/</loc>\n<lastmod>2024-01-01</lastmod>"
+        ));
+        assert!(sitemap.contains("<loc>http://localhost:8000/tags/test/</loc>"));
+        assert!(!sitemap.contains("hidden-post"));
+
+        let robots = fs::read_to_string(config.output_dir().join("robots.txt"))?;
+        assert!(robots.contains("Sitemap: http://localhost:8000/sitemap.xml"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_pages() -> Result<(), Error> {
+        let temp_dir = TempDir::new()?;
+        setup_test_site(&temp_dir)?;
+
+        fs::create_dir_all(temp_dir.path().join("pages/colophon"))?;
+        fs::write(
+            temp_dir.path().join("pages/about.md"),
+            "---\ntitle: \"About\"\n---\nAll about us",
+        )?;
+        fs::write(
+            temp_dir.path().join("pages/colophon/index.md"),
+            "---\ntitle: \"Colophon\"\nsitemap: false\n---\nBuilt with termv",
+        )?;
+
+        let config = create_test_config(&temp_dir);
+        let generator = SiteGenerator::new(&config)?;
+        generator.generate_site()?;
+
+        let output_dir = config.output_dir();
+        let about = fs::read_to_string(output_dir.join("about/index.html"))?;
+        assert!(about.contains("About:<p>All about us</p>"));
+        assert!(output_dir.join("colophon/index.html").exists());
+
+        let index = fs::read_to_string(output_dir.join("index.html"))?;
+        assert!(!index.contains("About"));
+        let rss = fs::read_to_string(output_dir.join("rss.xml"))?;
+        assert!(!rss.contains("/about/"));
+
+        let sitemap = fs::read_to_string(output_dir.join("sitemap.xml"))?;
+        assert!(sitemap.contains("<loc>http://localhost:8000/about/</loc>"));
+        assert!(!sitemap.contains("colophon"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_page_url_collisions() -> Result<(), Error> {
+        let temp_dir = TempDir::new()?;
+        setup_test_site(&temp_dir)?;
+        fs::create_dir_all(temp_dir.path().join("pages"))?;
+        for (file, path) in [
+            ("home.md", "/"),
+            ("feed.md", "rss.xml"),
+            ("tag.md", "/tags/test/"),
+            ("post.md", "/posts/test-post/"),
+        ] {
+            fs::write(
+                temp_dir.path().join("pages").join(file),
+                format!("---\ntitle: Clash\npath: \"{}\"\n---\nBody", path),
+            )?;
+        }
+
+        let config = create_test_config(&temp_dir);
+        let message = SiteGenerator::new(&config)?
+            .generate_site()
+            .unwrap_err()
+            .to_string();
+        assert!(message.starts_with("4 errors:"));
+        assert!(message.contains("the index and "));
+        assert!(message.contains("home.md are both published at /"));
+        assert!(message.contains("/rss.xml and "));
+        assert!(message.contains("the `test` tag page and "));
+        assert!(message.contains("post.md are both published at /posts/test-post/"));
+        // Nothing was written over
+        assert!(!config.output_dir().join("rss.xml").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unchanged_posts_are_not_rendered_again() -> Result<(), Error> {
+        let temp_dir = TempDir::new()?;
+        setup_test_site(&temp_dir)?;
+        let config = create_test_config(&temp_dir);
+        SiteGenerator::new(&config)?.generate_site()?;
+
+        // Doctor the cached rendering to see whether the next build uses it
+        let rendered_dir = temp_dir.path().join(".termv-cache/rendered");
+        let entries: Vec<PathBuf> = fs::read_dir(&rendered_dir)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<_, _>>()?;
+        assert_eq!(entries.len(), 1);
+        let json = fs::read_to_string(&entries[0])?;
+        fs::write(&entries[0], json.replace("Test body", "From the cache"))?;
+        fs::remove_file(config.output_dir().join("posts/test-post/index.html"))?;
+
+        SiteGenerator::new(&config)?.generate_site()?;
+        let page = config.output_dir().join("posts/test-post/index.html");
+        assert!(fs::read_to_string(&page)?.contains("From the cache"));
+
+        // Editing the post renders it afresh and drops the stale rendering
+        let source = config.posts_dir().join("test-post/post.md");
+        fs::write(
+            &source,
+            fs::read_to_string(&source)?.replace("Test body", "Edited"),
+        )?;
+        SiteGenerator::new(&config)?.generate_site()?;
+        assert!(fs::read_to_string(&page)?.contains("Edited"));
+        assert_eq!(fs::read_dir(&rendered_dir)?.count(), 1);
+        assert!(!entries[0].exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_prunes_outputs_no_longer_produced() -> Result<(), Error> {
+        let temp_dir = TempDir::new()?;
+        setup_test_site(&temp_dir)?;
+        let config = create_test_config(&temp_dir);
+        fs::write(
+            config.posts_dir().join("doomed.md"),
+            "---\ntitle: Doomed\ndate: 2024-01-02\ntags: [gone]\nslug: doomed\n---\nBody",
+        )?;
+        fs::write(config.static_dir().join("css/old.css"), "a {}")?;
+        SiteGenerator::new(&config)?.generate_site()?;
+
+        let output_dir = config.output_dir();
+        assert!(output_dir.join("posts/doomed/index.html").exists());
+        assert!(output_dir.join("tags/gone/index.html").exists());
+        // Files termv didn't write are none of its business
+        fs::write(output_dir.join("CNAME"), "example.com")?;
+
+        fs::remove_file(config.posts_dir().join("doomed.md"))?;
+        fs::remove_file(config.static_dir().join("css/old.css"))?;
+        SiteGenerator::new(&config)?.generate_site()?;
+
+        assert!(!output_dir.join("posts/doomed").exists());
+        assert!(!output_dir.join("tags/gone").exists());
+        assert!(!output_dir.join("css/old.css").exists());
+        assert!(output_dir.join("css/style.css").exists());
+        assert!(output_dir.join("posts/test-post/index.html").exists());
+        assert!(output_dir.join("CNAME").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_uncached_build_clears_the_cache() -> Result<(), Error> {
+        let temp_dir = TempDir::new()?;
+        setup_test_site(&temp_dir)?;
+        let mut config = create_test_config(&temp_dir);
+        let post = config.posts_dir().join("test-post/post.md");
+        let original = fs::read_to_string(&post)?;
+        let output = config.output_dir().join("posts/test-post/index.html");
+        SiteGenerator::new(&config)?.generate_site()?;
+
+        fs::write(&post, original.replace("Test body", "Edited body"))?;
+        config.build.cache = false;
+        SiteGenerator::new(&config)?.generate_site()?;
+        assert!(fs::read_to_string(&output)?.contains("Edited body"));
+        assert!(!BuildCache::path(&config).exists());
+
+        fs::write(&post, &original)?;
+        config.build.cache = true;
+        SiteGenerator::new(&config)?.generate_site()?;
+        assert!(fs::read_to_string(&output)?.contains("Test body"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_prunes_unused_diagrams() -> Result<(), Error> {
+        let temp_dir = TempDir::new()?;
+        setup_test_site(&temp_dir)?;
+        let mut config = create_test_config(&temp_dir);
+        config
+            .markdown
+            .diagrams
+            .insert("svg".to_string(), "cat".to_string());
+        let post = config.posts_dir().join("test-post/post.md");
+        let diagrams_dir = config.site_dir.join(CACHE_DIR).join(DIAGRAMS_DIR);
+        let cached = || -> Result<usize, Error> { Ok(fs::read_dir(&diagrams_dir)?.count()) };
+
+        let original = fs::read_to_string(&post)?;
+        fs::write(
+            &post,
+            format!("{}\n\n```svg\n<svg><g/></svg>\n```", original),
+        )?;
+        SiteGenerator::new(&config)?.generate_site()?;
+        assert_eq!(cached()?, 1);
+
+        // Still used by the post, though its cached rendering is reused
+        SiteGenerator::new(&config)?.generate_site()?;
+        assert_eq!(cached()?, 1);
+
+        fs::write(&post, original)?;
+        SiteGenerator::new(&config)?.generate_site()?;
+        assert_eq!(cached()?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_static_robots_txt_survives_rebuilds() -> Result<(), Error> {
+        let temp_dir = TempDir::new()?;
+        setup_test_site(&temp_dir)?;
+        let config = create_test_config(&temp_dir);
+        fs::write(
+            config.static_dir().join("robots.txt"),
+            "User-agent: *\nDisallow: /\n",
+        )?;
+
+        for _ in 0..2 {
+            SiteGenerator::new(&config)?.generate_site()?;
+            assert_eq!(
+                fs::read_to_string(config.output_dir().join("robots.txt"))?,
+                "User-agent: *\nDisallow: /\n"
+            );
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_copy_static_files() -> Result<(), Error> {
         let temp_dir = TempDir::new()?;
@@ -316,6 +1353,92 @@ mod tests {
             .exists());
         assert!(generator.config.output_dir().join("index.html").exists());
         assert!(generator.config.output_dir().join("css/style.css").exists());
+        assert!(generator.config.output_dir().join("rss.xml").exists());
+        assert!(generator.config.output_dir().join("atom.xml").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_incremental_build() -> Result<(), Error> {
+        let temp_dir = TempDir::new()?;
+        setup_test_site(&temp_dir)?;
+        let config = create_test_config(&temp_dir);
+
+        SiteGenerator::new(&config)?.generate_site()?;
+        assert!(BuildCache::path(&config).exists());
+
+        // Mark the outputs so we can tell whether they get rewritten
+        let post_path = config.output_dir().join("posts/test-post/index.html");
+        let css_path = config.output_dir().join("css/style.css");
+        fs::write(&post_path, "stale")?;
+        fs::write(&css_path, "stale")?;
+
+        let generator = SiteGenerator::new(&config)?;
+        generator.generate_site()?;
+        assert_eq!(fs::read_to_string(&post_path)?, "stale");
+        assert_eq!(fs::read_to_string(&css_path)?, "stale");
+
+        // Changing the post re-renders it, but leaves untouched files alone
+        let source = temp_dir.path().join("posts/test-post/post.md");
+        let content = fs::read_to_string(&source)?;
+        fs::write(&source, content.replace("Test body", "Changed body"))?;
+        SiteGenerator::new(&config)?.generate_site()?;
+        assert!(fs::read_to_string(&post_path)?.contains("Changed body"));
+        assert_eq!(fs::read_to_string(&css_path)?, "stale");
+
+        // Disabling the cache rebuilds everything
+        let mut config = config;
+        config.build.cache = false;
+        SiteGenerator::new(&config)?.generate_site()?;
+        assert_eq!(fs::read_to_string(&css_path)?, "body { color: black; }");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parallel_build_is_deterministic() -> Result<(), Error> {
+        let temp_dir = TempDir::new()?;
+        setup_test_site(&temp_dir)?;
+
+        for i in 0..20 {
+            fs::write(
+                temp_dir.path().join(format!("posts/post-{:02}.md", i)),
+                format!(
+                    "---\ntitle: \"Post {i:02}\"\ndate: 2024-02-01\nslug: \"post-{i:02}\"\n---\n```rust\nfn main() {{}}\n```"
+                ),
+            )?;
+        }
+
+        let mut config = create_test_config(&temp_dir);
+        config.build.cache = false;
+
+        let mut outputs = Vec::new();
+        for jobs in [1, 4] {
+            config.build.jobs = Some(jobs);
+            SiteGenerator::new(&config)?.generate_site()?;
+            outputs.push(fs::read_to_string(config.output_dir().join("index.html"))?);
+            assert!(config
+                .output_dir()
+                .join("posts/post-19/index.html")
+                .exists());
+        }
+
+        assert_eq!(outputs[0], outputs[1]);
+        assert!(outputs[0].find("Post 00") < outputs[0].find("Post 01"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_feeds_disabled() -> Result<(), Error> {
+        let temp_dir = TempDir::new()?;
+        setup_test_site(&temp_dir)?;
+        let mut config = create_test_config(&temp_dir);
+        config.feed.enabled = false;
+        let generator = SiteGenerator::new(&config)?;
+
+        generator.generate_site()?;
+
+        assert!(!config.output_dir().join("rss.xml").exists());
+        assert!(!config.output_dir().join("atom.xml").exists());
         Ok(())
     }
 
@@ -352,19 +1475,13 @@ New content"#,
         assert_eq!(posts.len(), 3);
         let sorted_posts = {
             let mut posts = posts;
-            posts.sort_by(|a, b| {
-                let parse_date = |date: &str| NaiveDate::parse_from_str(date, "%Y-%m-%d");
-                match (parse_date(&a.metadata.date), parse_date(&b.metadata.date)) {
-                    (Ok(date_a), Ok(date_b)) => date_b.cmp(&date_a),
-                    _ => b.metadata.date.cmp(&a.metadata.date),
-                }
-            });
+            posts.sort_by_key(|post| std::cmp::Reverse(post.metadata.date));
             posts
         };
 
-        assert_eq!(sorted_posts[0].metadata.date, "2024-01-02");
-        assert_eq!(sorted_posts[1].metadata.date, "2024-01-01");
-        assert_eq!(sorted_posts[2].metadata.date, "2023-12-31");
+        assert_eq!(sorted_posts[0].metadata.date.to_string(), "2024-01-02");
+        assert_eq!(sorted_posts[1].metadata.date.to_string(), "2024-01-01");
+        assert_eq!(sorted_posts[2].metadata.date.to_string(), "2023-12-31");
         Ok(())
     }
 
@@ -407,4 +1524,272 @@ New content"#,
 
         Ok(())
     }
+
+    #[test]
+    fn test_errors_are_listed_by_path() {
+        let paths = [
+            PathBuf::from("b.md"),
+            PathBuf::from("c.md"),
+            PathBuf::from("a.md"),
+        ];
+        let results: Vec<Result<(), Error>> = paths
+            .iter()
+            .map(|path| Err(Error::Permalink(path.display().to_string())))
+            .collect();
+
+        let Err(Error::Multiple(errors)) = all_or_errors(&paths, results) else {
+            panic!("expected every error to be reported");
+        };
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Permalink error: a.md",
+                "Permalink error: b.md",
+                "Permalink error: c.md"
+            ]
+        );
+
+        // As `main` prints them, without the variant names Debug would show
+        assert_eq!(
+            format!("{:?}", Error::Multiple(errors)),
+            "3 errors:\nPermalink error: a.md\nPermalink error: b.md\nPermalink error: c.md"
+        );
+        let template = Error::Template(tera::Error::chain(
+            "Failed to render 'post.html'",
+            "Variable `nope` not found",
+        ));
+        assert_eq!(
+            format!("{:?}", template),
+            "Template error: Failed to render 'post.html'\n  caused by: Variable `nope` not found"
+        );
+    }
+
+    #[test]
+    fn test_reports_every_frontmatter_error() -> Result<(), Error> {
+        let temp_dir = TempDir::new()?;
+        setup_test_site(&temp_dir)?;
+        let config = create_test_config(&temp_dir);
+        fs::write(
+            config.posts_dir().join("typo.md"),
+            "---\ntitle: \"Typo\"\ndate: 2024-01-01\nslug: typo\ntgas: [rust]\n---\nBody",
+        )?;
+        fs::write(
+            config.posts_dir().join("toml.md"),
+            "+++\ntitle = \"Toml\"\ndate = 2024-13-01\nslug = \"toml\"\n+++\nBody",
+        )?;
+        fs::create_dir_all(config.pages_dir())?;
+        fs::write(
+            config.pages_dir().join("about.md"),
+            "---\ntitel: About\n---\n",
+        )?;
+
+        let generator = SiteGenerator::new(&config)?;
+        let Err(Error::Multiple(errors)) = generator.generate_site() else {
+            panic!("expected every broken file to be reported");
+        };
+
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(messages.len(), 4, "{:#?}", messages);
+        assert!(messages[0].contains("toml.md:3: "));
+        assert!(messages[1].contains("typo.md:5: unknown key `tgas`, did you mean `tags`?"));
+        assert!(messages[2].contains("about.md:2: unknown key `titel`, did you mean `title`?"));
+        assert!(messages[3].contains("about.md:2: missing field `title`"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_extra_values_in_templates() -> Result<(), Error> {
+        let temp_dir = TempDir::new()?;
+        setup_test_site(&temp_dir)?;
+        let mut config = create_test_config(&temp_dir);
+        config
+            .extra
+            .insert("tagline".to_string(), tera::Value::from("Fast & loud"));
+        fs::write(
+            config.posts_dir().join("extra.md"),
+            "---\ntitle: Extra\ndate: 2024-01-01\nslug: extra\nextra:\n  cover: hero.png\n---\nBody",
+        )?;
+        fs::write(
+            config.templates_dir().join("post.html"),
+            "{{ post.metadata.extra.cover | default(value=\"none\") }} {{ config.extra.tagline }}",
+        )?;
+        fs::write(
+            config.templates_dir().join("index.html"),
+            "{% for post in posts %}{{ post.metadata.extra.cover | default(value=\"none\") }},{% endfor %} {{ config.extra.tagline }}",
+        )?;
+
+        SiteGenerator::new(&config)?.generate_site()?;
+
+        let post = fs::read_to_string(config.output_dir().join("posts/extra/index.html"))?;
+        assert_eq!(post, "hero.png Fast &amp; loud");
+        let index = fs::read_to_string(config.output_dir().join("index.html"))?;
+        assert!(index.contains("hero.png,"));
+        assert!(index.ends_with(" Fast &amp; loud"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_datetimes_and_timezone() -> Result<(), Error> {
+        let temp_dir = TempDir::new()?;
+        setup_test_site(&temp_dir)?;
+        let mut config = create_test_config(&temp_dir);
+        config.timezone = Some("Europe/Berlin".to_string());
+        for (slug, date) in [
+            ("morning", "2024-01-01 09:00"),
+            ("evening", "2024-01-01T18:30:00"),
+        ] {
+            fs::write(
+                config.posts_dir().join(format!("{}.md", slug)),
+                format!(
+                    "---\ntitle: {}\ndate: {}\nslug: {}\n---\nBody",
+                    slug, date, slug
+                ),
+            )?;
+        }
+        fs::write(
+            config.templates_dir().join("index.html"),
+            "{% for post in posts %}{{ post.metadata.slug }} {{ post.metadata.date | date(format=\"%H:%M %Z\") }}\n{% endfor %}",
+        )?;
+
+        SiteGenerator::new(&config)?.generate_site()?;
+
+        let index = fs::read_to_string(config.output_dir().join("index.html"))?;
+        assert_eq!(
+            index,
+            "evening 18:30 CET\nmorning 09:00 CET\ntest-post 00:00 CET\n"
+        );
+        let atom = fs::read_to_string(config.output_dir().join("atom.xml"))?;
+        assert!(atom.contains("<published>2024-01-01T18:30:00+01:00</published>"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_updated_dates() -> Result<(), Error> {
+        let temp_dir = TempDir::new()?;
+        setup_test_site(&temp_dir)?;
+        let mut config = create_test_config(&temp_dir);
+        config.build.updated_from_git = true;
+        fs::write(
+            config.posts_dir().join("revised.md"),
+            "---\ntitle: Revised\ndate: 2024-01-02\nupdated: 2024-02-01\nslug: revised\n---\nBody",
+        )?;
+        fs::write(
+            config.templates_dir().join("post.html"),
+            "{{ post.metadata.updated | default(value=\"never\") }}",
+        )?;
+
+        // The test post was committed after it was published, the other never
+        let repo = git2::Repository::init(temp_dir.path())?;
+        let mut index = repo.index()?;
+        index.add_path(Path::new("posts/test-post/post.md"))?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+        let time = git2::Time::new(1_709_640_000, 0); // 2024-03-05T12:00:00Z
+        let signature = git2::Signature::new("Test", "test@example.com", &time)?;
+        repo.commit(Some("HEAD"), &signature, &signature, "Edit", &tree, &[])?;
+
+        SiteGenerator::new(&config)?.generate_site()?;
+
+        let output = |path: &str| fs::read_to_string(config.output_dir().join(path));
+        assert_eq!(
+            output("posts/test-post/index.html")?,
+            "2024-03-05T12:00:00+00:00"
+        );
+        assert_eq!(output("posts/revised/index.html")?, "2024-02-01");
+
+        let atom = output("atom.xml")?;
+        assert!(atom.contains("<updated>2024-03-05T12:00:00+00:00</updated>\n<author>"));
+        assert!(atom.contains(
+            "<published>2024-01-02T00:00:00+00:00</published>\n<updated>2024-02-01T00:00:00+00:00</updated>"
+        ));
+        let sitemap = output("sitemap.xml")?;
+        assert!(sitemap.contains(
+            "<loc>http://localhost:8000/</loc>\n<lastmod>2024-03-05T12:00:00+00:00</lastmod>"
+        ));
+        assert!(sitemap.contains("posts/revised/</loc>\n<lastmod>2024-02-01</lastmod>"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_permalinks() -> Result<(), Error> {
+        let temp_dir = TempDir::new()?;
+        setup_test_site(&temp_dir)?;
+        let mut config = create_test_config(&temp_dir);
+        config.permalink = "/:year/:month/:slug/".to_string();
+        fs::write(
+            config.posts_dir().join("moved.md"),
+            "---\ntitle: Moved\ndate: 2024-02-01\ntags: [Rust]\nslug: moved\npermalink: /:tag/:slug/\n---\nBody",
+        )?;
+        fs::write(
+            config.templates_dir().join("index.html"),
+            "{% for post in posts %}{{ post.url | safe }} {% endfor %}",
+        )?;
+
+        SiteGenerator::new(&config)?.generate_site()?;
+
+        let output = |path: &str| fs::read_to_string(config.output_dir().join(path));
+        assert_eq!(output("index.html")?, "/rust/moved/ /2024/01/test-post/ ");
+        assert!(output("rust/moved/index.html")?.contains("Body"));
+        assert!(output("2024/01/test-post/index.html")?
+            .contains("src=\"/2024/01/test-post/assets/test-image.txt\""));
+        assert!(config
+            .output_dir()
+            .join("2024/01/test-post/assets/test-image.txt")
+            .exists());
+        assert!(output("sitemap.xml")?.contains("<loc>http://localhost:8000/rust/moved/</loc>"));
+        assert!(!config.output_dir().join("posts").exists());
+
+        fs::write(
+            config.posts_dir().join("clash.md"),
+            "---\ntitle: Clash\ndate: 2024-03-01\nslug: clash\npermalink: /2024/01/test-post\n---\nBody",
+        )?;
+        let err = SiteGenerator::new(&config)?.generate_site().unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("are both published at /2024/01/test-post/"));
+
+        // Posts can't take over the URLs of generated pages either
+        let clash = config.posts_dir().join("clash.md");
+        fs::write(
+            &clash,
+            "---\ntitle: Clash\ndate: 2024-03-01\nslug: clash\npermalink: /\n---\nBody",
+        )?;
+        let err = SiteGenerator::new(&config)?.generate_site().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Permalink error: the index and {} are both published at /",
+                clash.display()
+            )
+        );
+
+        fs::write(config.templates_dir().join("tag.html"), "{{ tag.name }}")?;
+        fs::write(config.templates_dir().join("tags.html"), "tags")?;
+        fs::write(
+            &clash,
+            "---\ntitle: Clash\ndate: 2024-03-01\nslug: clash\npermalink: /tags/rust/\n---\nBody",
+        )?;
+        let err = SiteGenerator::new(&config)?.generate_site().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Permalink error: the `Rust` tag page and {} are both published at /tags/rust/",
+                clash.display()
+            )
+        );
+
+        fs::write(
+            &clash,
+            "---\ntitle: Clash\ndate: 2024-03-01\nslug: clash\npermalink: ../../etc\n---\nBody",
+        )?;
+        let err = SiteGenerator::new(&config)?.generate_site().unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with(&format!("Frontmatter error in {}:", clash.display())));
+        assert!(err
+            .to_string()
+            .contains("`..` can't be used in `../../etc`"));
+        assert!(!temp_dir.path().join("etc").exists());
+        Ok(())
+    }
 }
@@ -1,6 +1,9 @@
+use chrono::{DateTime, FixedOffset, TimeZone};
 use git2::Config as GitConfig;
 use git2::Repository;
 use git2::RepositoryInitOptions;
+use git2::{Commit, DiffOptions, Sort};
+use std::collections::HashMap;
 use std::path::Path;
 
 use std::path::PathBuf;
@@ -69,3 +72,126 @@ pub fn init_git_repository(path: &Path) -> Result<Repository, Error> {
 
     Ok(repo)
 }
+
+// When each file was last committed, walking history back from HEAD of the
+// repository containing them. Files outside a repository or never committed are
+// left out.
+pub fn last_commit_times(
+    paths: &[PathBuf],
+) -> Result<HashMap<PathBuf, DateTime<FixedOffset>>, Error> {
+    let mut times = HashMap::new();
+    let Some(repo) = paths.first().and_then(|p| Repository::discover(p).ok()) else {
+        return Ok(times);
+    };
+    let Some(workdir) = repo.workdir().and_then(|w| w.canonicalize().ok()) else {
+        return Ok(times);
+    };
+
+    // Repository-relative path -> the path we were given
+    let mut wanted: HashMap<PathBuf, &PathBuf> = paths
+        .iter()
+        .filter_map(|path| {
+            let relative = path
+                .canonicalize()
+                .ok()?
+                .strip_prefix(&workdir)
+                .ok()?
+                .to_path_buf();
+            Some((relative, path))
+        })
+        .collect();
+
+    let mut revwalk = repo.revwalk()?;
+    if revwalk.push_head().is_err() {
+        // No commits yet
+        return Ok(times);
+    }
+    revwalk.set_sorting(Sort::TIME)?;
+
+    let mut diff_options = DiffOptions::new();
+    diff_options.disable_pathspec_match(true);
+    for path in wanted.keys() {
+        diff_options.pathspec(path);
+    }
+
+    for oid in revwalk {
+        if wanted.is_empty() {
+            break;
+        }
+        let commit = repo.find_commit(oid?)?;
+        let tree = commit.tree()?;
+        let parent_tree = match commit.parents().next() {
+            Some(parent) => Some(parent.tree()?),
+            None => None,
+        };
+        let diff =
+            repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut diff_options))?;
+
+        for delta in diff.deltas() {
+            let Some(path) = delta.new_file().path() else {
+                continue;
+            };
+            if let (Some(original), Some(time)) = (wanted.remove(path), commit_time(&commit)) {
+                times.insert(original.clone(), time);
+            }
+        }
+    }
+
+    Ok(times)
+}
+
+fn commit_time(commit: &Commit) -> Option<DateTime<FixedOffset>> {
+    let time = commit.time();
+    FixedOffset::east_opt(time.offset_minutes() * 60)?
+        .timestamp_opt(time.seconds(), 0)
+        .single()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::{Signature, Time};
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn commit_at(repo: &Repository, file: &str, seconds: i64) {
+        fs::write(repo.workdir().unwrap().join(file), seconds.to_string()).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(file)).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+
+        let signature =
+            Signature::new("Test", "test@example.com", &Time::new(seconds, 60)).unwrap();
+        let parent = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+        let parents: Vec<&Commit> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &signature, &signature, file, &tree, &parents)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_last_commit_times() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = Repository::init(temp_dir.path()).unwrap();
+        commit_at(&repo, "a.md", 1_700_000_000);
+        commit_at(&repo, "b.md", 1_700_100_000);
+        commit_at(&repo, "a.md", 1_700_200_000);
+        fs::write(temp_dir.path().join("new.md"), "uncommitted").unwrap();
+
+        let paths: Vec<PathBuf> = ["a.md", "b.md", "new.md"]
+            .iter()
+            .map(|file| temp_dir.path().join(file))
+            .collect();
+        let times = last_commit_times(&paths).unwrap();
+
+        assert_eq!(times[&paths[0]].timestamp(), 1_700_200_000);
+        assert_eq!(times[&paths[0]].offset().local_minus_utc(), 3600);
+        assert_eq!(times[&paths[1]].timestamp(), 1_700_100_000);
+        assert!(!times.contains_key(&paths[2]));
+
+        let outside = TempDir::new().unwrap();
+        assert!(last_commit_times(&[outside.path().join("x.md")])
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -9,6 +9,7 @@ pub fn create_directory_structure(path: &PathBuf) -> Result<(), Error> {
         path,
         &path.join("posts"),
         &path.join("posts/example/assets"),
+        &path.join("pages"),
         &path.join("templates"),
         &path.join("static"),
         &path.join("static/css"),
@@ -29,6 +30,18 @@ pub fn create_directory_structure(path: &PathBuf) -> Result<(), Error> {
             "templates/index.html",
             include_str!(concat!(env!("OUT_DIR"), "/templates/index.html")),
         ),
+        (
+            "templates/page.html",
+            include_str!(concat!(env!("OUT_DIR"), "/templates/page.html")),
+        ),
+        (
+            "templates/tag.html",
+            include_str!(concat!(env!("OUT_DIR"), "/templates/tag.html")),
+        ),
+        (
+            "templates/tags.html",
+            include_str!(concat!(env!("OUT_DIR"), "/templates/tags.html")),
+        ),
         (
             "templates/base.html",
             include_str!(concat!(env!("OUT_DIR"), "/templates/base.html")),
@@ -39,6 +52,12 @@ pub fn create_directory_structure(path: &PathBuf) -> Result<(), Error> {
         fs::write(path.join(file), content)?;
     }
 
+    // Create example shortcode component
+    fs::write(
+        path.join("components/figure.html"),
+        include_str!(concat!(env!("OUT_DIR"), "/templates/figure.html")),
+    )?;
+
     // Create default stylesheet
     fs::write(
         path.join("static/css/style.css"),
@@ -51,6 +70,12 @@ pub fn create_directory_structure(path: &PathBuf) -> Result<(), Error> {
         include_str!(concat!(env!("OUT_DIR"), "/templates/example.md")),
     )?;
 
+    // Create example page
+    fs::write(
+        path.join("pages/about.md"),
+        include_str!(concat!(env!("OUT_DIR"), "/templates/about.md")),
+    )?;
+
     // Create config file
     fs::write(
         path.join("config.toml"),
@@ -65,6 +90,7 @@ pub fn create_directory_structure(path: &PathBuf) -> Result<(), Error> {
 
     // Create .gitignore
     let gitignore_content = r#"dist/
+    .termv-cache/
     target/
     **/.DS_Store
     .env"#;
@@ -1,17 +1,31 @@
 pub mod anthropic;
+pub mod cache;
 pub mod config;
 pub mod constants;
+pub mod date;
+pub mod diagram;
 pub mod errors;
+pub mod feed;
+pub mod frontmatter;
 pub mod generator;
 pub mod git;
 pub mod init;
+pub mod livereload;
 pub mod markdown;
+pub mod math;
+pub mod overlay;
+pub mod page;
+pub mod paginator;
+pub mod permalink;
 pub mod post;
 pub mod serve;
+pub mod shortcode;
+pub mod sitemap;
+pub mod tags;
 
 #[cfg(test)]
 pub mod tests {
-    use crate::config::{Author, BuildConfig, Config, ServerConfig};
+    use crate::config::{Author, BuildConfig, Config, FeedConfig, MarkdownConfig, ServerConfig};
     use crate::post::{Post, PostMetadata};
     use std::fs;
     use tempfile::TempDir;
@@ -23,6 +37,8 @@ pub mod tests {
             base_url: "http://localhost:8000".to_string(),
             title: "Test Blog".to_string(),
             description: "Test Description".to_string(),
+            timezone: None,
+            permalink: crate::permalink::DEFAULT.to_string(),
             author: Author {
                 name: "Test Author".to_string(),
                 email: "test@example.com".to_string(),
@@ -32,13 +48,25 @@ pub mod tests {
                 port: 8000,
                 hot_reload: true,
             },
+            feed: FeedConfig::default(),
+            markdown: MarkdownConfig::default(),
+            extra: Default::default(),
             build: BuildConfig {
                 verbose: false,
                 output_dir: "dist".to_string(),
                 posts_dir: "posts".to_string(),
+                pages_dir: "pages".to_string(),
                 templates_dir: "templates".to_string(),
+                components_dir: "components".to_string(),
+                syntaxes_dir: "syntaxes".to_string(),
                 static_dir: "static".to_string(),
                 post_assets_dir: "assets".to_string(),
+                paginate_by: None,
+                drafts: false,
+                future: false,
+                cache: true,
+                jobs: None,
+                updated_from_git: false,
             },
         }
     }
@@ -53,15 +81,25 @@ pub mod tests {
         let test_post = Post {
             metadata: PostMetadata {
                 title: "Test Post".to_string(),
-                date: "2024-01-01".to_string(),
+                date: "2024-01-01".parse().unwrap(),
+                updated: None,
                 author: "Test Author".to_string(),
                 tags: vec!["test".to_string()],
                 preview: "Test preview".to_string(),
                 slug: "test-post".to_string(),
                 read_time: 0,
+                draft: false,
+                sitemap: true,
+                permalink: None,
+                extra: Default::default(),
             },
+            url: "/posts/test-post/".to_string(),
+            source: Default::default(),
             content: String::new(),
             html_content: String::new(),
+            summary: String::new(),
+            toc: Vec::new(),
+            diagrams: Vec::new(),
         };
 
         let config = create_test_config(temp_dir);
@@ -104,6 +142,18 @@ Test body
             temp_dir.path().join("templates/index.html"),
             "{% extends \"base.html\" %}{% block content %}{% for post in posts %}{{ post.metadata.title }}{% endfor %}{% endblock %}",
         )?;
+        fs::write(
+            temp_dir.path().join("templates/page.html"),
+            "{% extends \"base.html\" %}{% block content %}{{ page.metadata.title }}:{{ page.html_content | safe }}{% endblock %}",
+        )?;
+        fs::write(
+            temp_dir.path().join("templates/tag.html"),
+            "{% extends \"base.html\" %}{% block content %}{{ tag.name }}{% for post in posts %}{{ post.metadata.title }}{% endfor %}{% endblock %}",
+        )?;
+        fs::write(
+            temp_dir.path().join("templates/tags.html"),
+            "{% extends \"base.html\" %}{% block content %}{% for tag in tags %}{{ tag.name }}:{{ tag.count }}{% endfor %}{% endblock %}",
+        )?;
 
         // Create test static file
         fs::write(
@@ -0,0 +1,181 @@
+use actix_web::body::{to_bytes, BoxBody, MessageBody};
+use actix_web::dev::ServiceResponse;
+use actix_web::http::header::{HeaderValue, CONTENT_LENGTH, CONTENT_TYPE};
+use actix_web::http::StatusCode;
+use actix_web::web::{Bytes, Data};
+use actix_web::HttpResponse;
+use std::path::PathBuf;
+use tokio::sync::broadcast;
+
+// Endpoint the injected client listens on for Server-Sent Events
+pub const LIVE_RELOAD_PATH: &str = "/__termv/livereload";
+
+const CLIENT_SCRIPT: &str = r#"<script>
+(() => {
+  const source = new EventSource("/__termv/livereload");
+  source.onmessage = (event) => {
+    if (event.data === "css") {
+      document.querySelectorAll('link[rel="stylesheet"]').forEach((link) => {
+        const url = new URL(link.href);
+        url.searchParams.set("termv-reload", Date.now());
+        link.href = url.toString();
+      });
+    } else {
+      window.location.reload();
+    }
+  };
+})();
+</script>"#;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReloadKind {
+    // Only stylesheets changed, so they can be swapped without a page reload
+    Css,
+    Full,
+}
+
+impl ReloadKind {
+    pub fn for_changes(paths: &[PathBuf]) -> Self {
+        if !paths.is_empty()
+            && paths
+                .iter()
+                .all(|p| p.extension().is_some_and(|ext| ext == "css"))
+        {
+            ReloadKind::Css
+        } else {
+            ReloadKind::Full
+        }
+    }
+
+    fn as_event(&self) -> &'static str {
+        match self {
+            ReloadKind::Css => "css",
+            ReloadKind::Full => "reload",
+        }
+    }
+}
+
+// Fans rebuild notifications out to every connected browser
+#[derive(Clone)]
+pub struct LiveReload {
+    tx: broadcast::Sender<ReloadKind>,
+}
+
+impl LiveReload {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(16);
+        Self { tx }
+    }
+
+    pub fn notify(&self, kind: ReloadKind) {
+        // No receivers just means no browser is connected
+        let _ = self.tx.send(kind);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ReloadKind> {
+        self.tx.subscribe()
+    }
+}
+
+impl Default for LiveReload {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub async fn events(live_reload: Data<LiveReload>) -> HttpResponse {
+    let rx = live_reload.subscribe();
+
+    let stream = futures_util::stream::unfold(rx, |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(kind) => {
+                    let event = Bytes::from(format!("data: {}\n\n", kind.as_event()));
+                    return Some((Ok::<_, actix_web::Error>(event), rx));
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(stream)
+}
+
+pub fn inject_script(html: &str) -> String {
+    match html.rfind("</body>") {
+        Some(idx) => format!("{}{}\n{}", &html[..idx], CLIENT_SCRIPT, &html[idx..]),
+        None => format!("{}\n{}", html, CLIENT_SCRIPT),
+    }
+}
+
+pub fn is_html(res: &ServiceResponse<impl MessageBody>) -> bool {
+    res.headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/html"))
+}
+
+// Add the live reload client to HTML responses on their way out. Built files
+// on disk are left untouched.
+pub async fn inject_into_response<B>(
+    res: ServiceResponse<B>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error>
+where
+    B: MessageBody + 'static,
+{
+    // Leave 304s, range requests and the like alone
+    if res.status() != StatusCode::OK || !is_html(&res) {
+        return Ok(res.map_into_boxed_body());
+    }
+
+    let (req, res) = res.into_parts();
+    let (mut res, body) = res.into_parts();
+    let bytes = to_bytes(body)
+        .await
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Failed to read response"))?;
+
+    let html = inject_script(&String::from_utf8_lossy(&bytes));
+    res.headers_mut().remove(CONTENT_LENGTH);
+    res.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("text/html; charset=utf-8"),
+    );
+
+    Ok(ServiceResponse::new(
+        req,
+        res.set_body(html).map_into_boxed_body(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inject_script() {
+        let html = "<html><body><p>Hi</p></body></html>";
+        let injected = inject_script(html);
+        assert!(injected.contains("<p>Hi</p><script>"));
+        assert!(injected.ends_with("</script>\n</body></html>"));
+
+        let fragment = inject_script("<p>No body</p>");
+        assert!(fragment.starts_with("<p>No body</p>\n<script>"));
+    }
+
+    #[test]
+    fn test_reload_kind_for_changes() {
+        let css = vec![PathBuf::from("static/css/style.css")];
+        assert_eq!(ReloadKind::for_changes(&css), ReloadKind::Css);
+
+        let mixed = vec![
+            PathBuf::from("static/css/style.css"),
+            PathBuf::from("posts/hello.md"),
+        ];
+        assert_eq!(ReloadKind::for_changes(&mixed), ReloadKind::Full);
+        assert_eq!(ReloadKind::for_changes(&[]), ReloadKind::Full);
+    }
+}
@@ -60,6 +60,18 @@ enum Commands {
 
         #[arg(long, default_value = "true")]
         auto_build: Option<bool>,
+
+        /// Include posts marked as drafts
+        #[arg(long)]
+        drafts: bool,
+
+        /// Include posts dated in the future
+        #[arg(long)]
+        future: bool,
+
+        /// Ignore the build cache and rebuild everything
+        #[arg(long)]
+        no_cache: bool,
     },
     /// Build the site
     Build {
@@ -71,6 +83,22 @@ enum Commands {
 
         #[arg(short, long)]
         verbose: Option<bool>,
+
+        /// Include posts marked as drafts
+        #[arg(long)]
+        drafts: bool,
+
+        /// Include posts dated in the future
+        #[arg(long)]
+        future: bool,
+
+        /// Ignore the build cache and rebuild everything
+        #[arg(long)]
+        no_cache: bool,
+
+        /// Number of threads used to render posts (default: one per core)
+        #[arg(short, long)]
+        jobs: Option<usize>,
     },
 }
 
@@ -109,11 +137,19 @@ fn main() -> Result<(), Error> {
             dir,
             output_path,
             verbose,
+            drafts,
+            future,
+            no_cache,
+            jobs,
         } => {
             let site_dir = dir.unwrap_or_else(|| PathBuf::from("."));
             let config = Config::load(&site_dir)?.with_overrides(ConfigOverrides {
                 output_dir: output_path,
                 verbose,
+                drafts: drafts.then_some(true),
+                future: future.then_some(true),
+                cache: no_cache.then_some(false),
+                jobs,
                 ..Default::default()
             });
 
@@ -142,6 +178,9 @@ fn main() -> Result<(), Error> {
             hot_reload,
             verbose,
             auto_build,
+            drafts,
+            future,
+            no_cache,
         } => {
             let site_dir = dir.unwrap_or_else(|| PathBuf::from("."));
             let config = Config::load(&site_dir)?.with_overrides(ConfigOverrides {
@@ -149,7 +188,9 @@ fn main() -> Result<(), Error> {
                 verbose,
                 hot_reload,
                 auto_build,
-
+                drafts: drafts.then_some(true),
+                future: future.then_some(true),
+                cache: no_cache.then_some(false),
                 ..Default::default()
             });
 
@@ -1,12 +1,116 @@
-use pulldown_cmark::{html, CodeBlockKind, Event, Options, Parser as MarkdownParser, Tag, TagEnd};
+use console::Style;
+use pulldown_cmark::{
+    html, BlockQuoteKind, CodeBlockKind, CowStr, Event, Options, Parser as MarkdownParser, Tag,
+    TagEnd,
+};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::sync::{Mutex, PoisonError};
 
+use syntect::dumps::{dump_to_uncompressed_file, from_uncompressed_dump_file};
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::html::css_for_theme_with_class_style;
 use syntect::html::{ClassStyle, ClassedHTMLGenerator};
-use syntect::parsing::SyntaxSet;
+use syntect::parsing::syntax_definition::{ContextReference, MatchOperation, Pattern};
+use syntect::parsing::{SyntaxDefinition, SyntaxSet, SyntaxSetBuilder};
 use syntect::util::LinesWithEndings;
+use walkdir::WalkDir;
+
+use crate::cache::{hash_bytes, CACHE_DIR, DIAGRAMS_DIR};
+use crate::config::Config;
+use crate::diagram::{CommandRenderer, DiagramRenderer};
+use crate::errors::Error;
+use crate::feed::escape_xml;
+use crate::math::{self, MathError};
+use crate::post::slugify;
+use crate::shortcode::{self, Components, Segment, Shortcode};
+
+// Where markdown being rendered came from, so errors can point at it
+#[derive(Debug, Clone, Copy)]
+pub struct Source<'a> {
+    pub file: &'a str,
+    // Line of the file the markdown starts on
+    pub line: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TocEntry {
+    pub level: u32,
+    pub text: String,
+    pub id: String,
+    pub children: Vec<TocEntry>,
+}
+
+#[derive(Debug)]
+pub struct RenderedMarkdown {
+    pub html: String,
+    // The excerpt: everything before a `<!-- more -->` line, or the first paragraph
+    pub summary: String,
+    // Headings nested by level, e.g. h3s under the preceding h2
+    pub toc: Vec<TocEntry>,
+    // Cache keys of the diagrams in the document
+    pub diagrams: Vec<String>,
+}
+
+// Hands out heading anchors, suffixing repeats ("setup", "setup-1", ...) so
+// every id in a document is unique
+#[derive(Default)]
+struct HeadingIds {
+    seen: HashMap<String, usize>,
+}
+
+impl HeadingIds {
+    // Reserve an explicit `{#id}`, so no generated id takes it. False if it
+    // was already taken.
+    fn claim(&mut self, id: &str) -> bool {
+        if self.seen.contains_key(id) {
+            return false;
+        }
+        self.seen.insert(id.to_string(), 0);
+        true
+    }
+
+    fn unique(&mut self, text: &str) -> String {
+        let mut base = slugify(text);
+        if base.is_empty() {
+            base = "section".to_string();
+        }
+
+        let mut id = base.clone();
+        while let Some(count) = self.seen.get_mut(&id) {
+            *count += 1;
+            id = format!("{}-{}", base, count);
+        }
+        self.seen.insert(id.clone(), 0);
+        id
+    }
+}
+
+// What rendering a document shares with the shortcode bodies in it
+#[derive(Default)]
+struct DocumentState {
+    heading_ids: HeadingIds,
+    diagrams: Vec<String>,
+}
+
+// Bundled syntaxes merged with site syntaxes that include them, and the hash of
+// the site syntaxes. Merging takes a while, so `serve` rebuilds reuse it.
+static MERGED_SYNTAXES: Mutex<Option<(String, SyntaxSet)>> = Mutex::new(None);
 
 pub struct MarkdownProcessor {
     syntax_set: SyntaxSet,
+    // Site syntaxes that stand on their own, looked up before `syntax_set`
+    site_syntaxes: SyntaxSet,
     options: Options,
+    components: Components,
+    // Fence language -> syntax to highlight it with
+    language_aliases: BTreeMap<String, String>,
+    // Fence language -> renderer turning the block into a diagram instead
+    diagrams: HashMap<String, Box<dyn DiagramRenderer>>,
 }
 
 impl MarkdownProcessor {
@@ -16,17 +120,173 @@ impl MarkdownProcessor {
         options.insert(Options::ENABLE_TABLES);
         options.insert(Options::ENABLE_FOOTNOTES);
         options.insert(Options::ENABLE_TASKLISTS);
+        // GitHub-style `> [!NOTE]` alerts
+        options.insert(Options::ENABLE_GFM);
+        // `## Heading {#custom-id}`
+        options.insert(Options::ENABLE_HEADING_ATTRIBUTES);
 
         // Initialize syntax highlighting
         let syntax_set = SyntaxSet::load_defaults_newlines();
 
         Self {
             syntax_set,
+            site_syntaxes: SyntaxSet::default(),
             options,
+            components: Components::default(),
+            language_aliases: BTreeMap::new(),
+            diagrams: HashMap::new(),
+        }
+    }
+
+    // A processor for a site, with its shortcode components, extra syntax
+    // definitions and language aliases
+    pub fn from_config(config: &Config) -> Result<Self, Error> {
+        let mut processor = Self::with_components(&config.components_dir())?;
+        let cache_dir = |name: &str| {
+            config
+                .build
+                .cache
+                .then(|| config.site_dir.join(CACHE_DIR).join(name))
+        };
+        processor.add_syntaxes(&config.syntaxes_dir(), cache_dir("syntaxes").as_deref())?;
+        processor.language_aliases = config.markdown.language_aliases.clone();
+        if config.markdown.math {
+            // `$inline$` and `$$display$$` math, rendered to MathML
+            processor.options.insert(Options::ENABLE_MATH);
         }
+
+        let cache_dir = cache_dir(DIAGRAMS_DIR);
+        for (language, command) in &config.markdown.diagrams {
+            processor.register_diagram(
+                language,
+                Box::new(CommandRenderer::new(command, cache_dir.clone())),
+            );
+        }
+        Ok(processor)
     }
 
-    pub fn render(&self, content: &str) -> String {
+    // Render ```language blocks with `renderer` instead of highlighting them
+    pub fn register_diagram(&mut self, language: &str, renderer: Box<dyn DiagramRenderer>) {
+        self.diagrams.insert(language.to_string(), renderer);
+    }
+
+    // A processor that renders shortcodes with the templates in `dir`
+    pub fn with_components(dir: &Path) -> Result<Self, Error> {
+        Ok(Self {
+            components: Components::load(dir)?,
+            ..Self::new()
+        })
+    }
+
+    // Load the .sublime-syntax files in `dir`. Definitions that stand on their
+    // own are built by themselves. Ones that include a bundled syntax have to be
+    // merged with all of them, which is slow, so the merged set is kept in
+    // memory and as a dump in `dump_dir`.
+    fn add_syntaxes(&mut self, dir: &Path, dump_dir: Option<&Path>) -> Result<(), Error> {
+        let mut definitions = Vec::new();
+        let mut sources = env!("CARGO_PKG_VERSION").as_bytes().to_vec();
+        for entry in WalkDir::new(dir)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| {
+                e.path()
+                    .extension()
+                    .is_some_and(|ext| ext == "sublime-syntax")
+            })
+        {
+            let path = entry.path();
+            let text = fs::read_to_string(path)?;
+            let name = path.file_stem().and_then(|stem| stem.to_str());
+            let definition = SyntaxDefinition::load_from_str(&text, true, name)
+                .map_err(|e| Error::SyntaxDefinition(format!("{}: {}", path.display(), e)))?;
+            sources.extend_from_slice(path.to_string_lossy().as_bytes());
+            sources.extend_from_slice(text.as_bytes());
+            definitions.push(definition);
+        }
+
+        if definitions.is_empty() {
+            return Ok(());
+        }
+        if !uses_bundled_syntaxes(&definitions) {
+            let mut builder = SyntaxSetBuilder::new();
+            for definition in definitions {
+                builder.add(definition);
+            }
+            self.site_syntaxes = builder.build();
+            return Ok(());
+        }
+
+        let key = hash_bytes(&sources);
+        let mut merged = MERGED_SYNTAXES
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some((_, set)) = merged.as_ref().filter(|(cached, _)| *cached == key) {
+            self.syntax_set = set.clone();
+            return Ok(());
+        }
+
+        let dump = dump_dir.map(|dir| dir.join(format!("{}.packdump", key)));
+        let set = match dump
+            .as_ref()
+            .and_then(|path| from_uncompressed_dump_file(path).ok())
+        {
+            Some(set) => set,
+            None => {
+                let mut builder = std::mem::take(&mut self.syntax_set).into_builder();
+                for definition in definitions {
+                    builder.add(definition);
+                }
+                let set = builder.build();
+                if let (Some(dir), Some(path)) = (dump_dir, &dump) {
+                    // Only the dump for the current definitions is worth keeping
+                    let _ = fs::remove_dir_all(dir);
+                    fs::create_dir_all(dir)?;
+                    dump_to_uncompressed_file(&set, path).map_err(|e| {
+                        Error::SyntaxDefinition(format!("{}: {}", path.display(), e))
+                    })?;
+                }
+                set
+            }
+        };
+
+        *merged = Some((key, set.clone()));
+        self.syntax_set = set;
+        Ok(())
+    }
+
+    pub fn render(&self, content: &str) -> Result<String, Error> {
+        let source = Source {
+            file: "<markdown>",
+            line: 1,
+        };
+        Ok(self.render_document(content, &source)?.html)
+    }
+
+    // Render markdown to HTML, giving every heading an anchor id and
+    // collecting them into a table of contents. A `[toc]` paragraph is
+    // replaced with the rendered table of contents.
+    pub fn render_document(
+        &self,
+        content: &str,
+        source: &Source,
+    ) -> Result<RenderedMarkdown, Error> {
+        let mut state = DocumentState::default();
+        let rendered = self.render_with_ids(content, source, &mut state)?;
+        Ok(RenderedMarkdown {
+            diagrams: state.diagrams,
+            ..rendered
+        })
+    }
+
+    // Shortcode bodies are rendered with the state of the document around
+    // them, so a heading inside one can't repeat an id outside it
+    fn render_with_ids(
+        &self,
+        content: &str,
+        source: &Source,
+        state: &mut DocumentState,
+    ) -> Result<RenderedMarkdown, Error> {
         let content_without_frontmatter = if let Some(stripped) = content.strip_prefix("---") {
             if let Some(end_idx) = stripped.find("---") {
                 stripped[end_idx + 3..].trim_start()
@@ -37,75 +297,784 @@ impl MarkdownProcessor {
             content
         };
 
-        let parser = MarkdownParser::new_ext(content_without_frontmatter, self.options);
+        let stripped = &content[..content.len() - content_without_frontmatter.len()];
+        let first_line = source.line + stripped.matches('\n').count();
+        let (expanded, calls) =
+            self.expand_shortcodes(content_without_frontmatter, source.file, first_line)?;
+
+        let markdown = expand_fenced_admonitions(&expanded);
+        let parser = MarkdownParser::new_ext(&markdown, self.options);
         let mut events = Vec::new();
         let mut code_buffer = String::new();
         let mut in_code_block = false;
-        let mut current_lang = None;
+        let mut current_fence = None;
+        let mut headings = Vec::new();
+        // Events of the heading being read, held back until its text (and so its id) is known
+        let mut heading_events: Option<Vec<Event>> = None;
+        let mut heading_text = String::new();
+
+        // Math and diagram errors point at the first occurrence of the failing
+        // source; identical source earlier in the post would have failed the same way
+        let line_of = |needle: &str| {
+            first_line
+                + content_without_frontmatter
+                    .find(needle)
+                    .map_or(0, |offset| {
+                        content_without_frontmatter[..offset].matches('\n').count()
+                    })
+        };
+        // Math using commands the converter doesn't know is kept as TeX for the
+        // reader's browser to render, with a warning; broken math fails the build
+        let render_math = |tex: &str, display: bool| {
+            let delimiter = if display { "$$" } else { "$" };
+            match math::to_mathml(tex, display) {
+                Ok(mathml) => Ok(mathml),
+                Err(MathError::Unsupported(message)) => {
+                    println!(
+                        "{}",
+                        Style::new().yellow().apply_to(format!(
+                            "⚠️  {}:{}: {} in `{}{}{}`, left as TeX",
+                            source.file,
+                            line_of(tex),
+                            message,
+                            delimiter,
+                            tex,
+                            delimiter
+                        ))
+                    );
+                    Ok(math::raw_tex(tex, display))
+                }
+                Err(MathError::Malformed(message)) => Err(Error::Math {
+                    file: source.file.to_string(),
+                    line: line_of(tex),
+                    message: format!("{} in `{}{}{}`", message, delimiter, tex, delimiter),
+                }),
+            }
+        };
+
+        // Explicit ids are used as written, so they are claimed before any
+        // heading gets a generated one
+        let parsed: Vec<Event> = parser.collect();
+        for event in &parsed {
+            if let Event::Start(Tag::Heading { id: Some(id), .. }) = event {
+                if !state.heading_ids.claim(id) {
+                    return Err(Error::Heading {
+                        file: source.file.to_string(),
+                        line: first_line
+                            + content_without_frontmatter
+                                .rfind(&format!("#{}", id))
+                                .map_or(0, |offset| {
+                                    content_without_frontmatter[..offset].matches('\n').count()
+                                }),
+                        message: format!("the id `{}` is used by more than one heading", id),
+                    });
+                }
+            }
+        }
+        let shortcodes = self.render_shortcodes(
+            content_without_frontmatter,
+            calls,
+            source.file,
+            first_line,
+            state,
+        )?;
 
         // First pass: collect and process events
-        for event in parser {
+        for event in parsed {
+            let event = match event {
+                Event::InlineMath(tex) => Event::InlineHtml(render_math(&tex, false)?.into()),
+                Event::DisplayMath(tex) => Event::InlineHtml(render_math(&tex, true)?.into()),
+                event => event,
+            };
+
             match event {
                 Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(lang))) => {
                     in_code_block = true;
-                    current_lang = Some(lang.as_ref().to_string());
+                    current_fence = Some(CodeFence::parse(&lang));
                     continue;
                 }
                 Event::End(TagEnd::CodeBlock) => {
-                    // Add the highlighted code block as a raw HTML event
-                    let highlighted = self.highlight_code(&code_buffer, current_lang.as_deref());
-                    events.push(Event::Html(highlighted.into()));
+                    // Add the highlighted code block (or rendered diagram) as a raw HTML event
+                    let fence = current_fence.take().unwrap_or_default();
+                    let html = match self.diagram_renderer(&fence) {
+                        Some((language, renderer)) => {
+                            let svg = renderer.render(&code_buffer).map_err(|message| {
+                                Error::Diagram {
+                                    file: source.file.to_string(),
+                                    line: line_of(&code_buffer),
+                                    message,
+                                }
+                            })?;
+                            state.diagrams.extend(renderer.cache_key(&code_buffer));
+                            diagram_figure(language, svg, &fence)
+                        }
+                        None => self.highlight_code(&code_buffer, &fence),
+                    };
+                    events.push(Event::Html(html.into()));
                     code_buffer.clear();
                     in_code_block = false;
-                    current_lang = None;
                     continue;
                 }
-                Event::Text(ref text) => {
-                    if in_code_block {
-                        code_buffer.push_str(text);
-                        continue;
+                Event::Text(ref text) if in_code_block => {
+                    code_buffer.push_str(text);
+                    continue;
+                }
+                Event::Start(Tag::BlockQuote(Some(kind))) => {
+                    let kind = alert_kind(kind);
+                    events.push(Event::Html(admonition_open(kind, None).into()));
+                    continue;
+                }
+                Event::End(TagEnd::BlockQuote(Some(_))) => {
+                    events.push(Event::Html("</aside>\n".into()));
+                    continue;
+                }
+                Event::Start(Tag::Heading { .. }) => {
+                    heading_events = Some(vec![event]);
+                    heading_text.clear();
+                    continue;
+                }
+                Event::End(TagEnd::Heading(level)) => {
+                    let mut buffered = heading_events.take().unwrap_or_default();
+                    if let Some(Event::Start(Tag::Heading { id, .. })) = buffered.first_mut() {
+                        // A shortcode in the heading counts as the text it renders to
+                        let mut text = heading_text.trim().to_string();
+                        for (i, rendered) in shortcodes.iter().enumerate() {
+                            let placeholder = shortcode_placeholder(i);
+                            if text.contains(&placeholder) {
+                                text = text.replace(&placeholder, &html_text(rendered));
+                            }
+                        }
+                        let anchor = match id {
+                            Some(explicit) => explicit.to_string(),
+                            None => state.heading_ids.unique(&text),
+                        };
+                        *id = Some(CowStr::from(anchor.clone()));
+                        headings.push(TocEntry {
+                            level: level as u32,
+                            text: text.trim().to_string(),
+                            id: anchor,
+                            children: Vec::new(),
+                        });
                     }
+                    events.extend(buffered);
+                    events.push(event);
+                    continue;
                 }
                 _ => {}
             }
 
             if !in_code_block {
-                events.push(event);
+                match heading_events.as_mut() {
+                    Some(buffered) => {
+                        if let Event::Text(text) | Event::Code(text) = &event {
+                            heading_text.push_str(text);
+                        }
+                        buffered.push(event);
+                    }
+                    None => events.push(event),
+                }
             }
         }
 
+        let toc = nest_headings(headings);
+        // Put back what was held out of the markdown: shortcode HTML and the toc,
+        // which excerpts leave out
+        let finish = |mut html: String, toc_html: &str| {
+            for (i, rendered) in shortcodes.iter().enumerate() {
+                let placeholder = shortcode_placeholder(i);
+                html = html
+                    .replace(&format!("<p>{}</p>", placeholder), rendered)
+                    .replace(&placeholder, rendered);
+            }
+            for marker in ["<p>[toc]</p>", "<p>[TOC]</p>"] {
+                if html.contains(marker) {
+                    html = html.replace(marker, toc_html);
+                }
+            }
+            html
+        };
+
+        // Listing pages show many summaries at once, so their headings leave
+        // out the ids they have on the post's own page
+        let mut summary = String::new();
+        if let Some(end) = summary_end(&events) {
+            let without_ids = events[..end].iter().cloned().map(|event| match event {
+                Event::Start(Tag::Heading {
+                    level,
+                    classes,
+                    attrs,
+                    ..
+                }) => Event::Start(Tag::Heading {
+                    level,
+                    id: None,
+                    classes,
+                    attrs,
+                }),
+                event => event,
+            });
+            html::push_html(&mut summary, without_ids);
+        }
+
         // Second pass: render all events to HTML
         let mut html_output = String::new();
         html::push_html(&mut html_output, events.into_iter());
-        html_output
+
+        Ok(RenderedMarkdown {
+            html: finish(html_output, &toc_html(&toc)),
+            summary: finish(summary, "").trim().to_string(),
+            toc,
+            diagrams: Vec::new(),
+        })
+    }
+
+    fn diagram_renderer<'f>(
+        &self,
+        fence: &'f CodeFence,
+    ) -> Option<(&'f str, &dyn DiagramRenderer)> {
+        let language = fence.language.as_deref()?;
+        let renderer = self.diagrams.get(language)?;
+        Some((language, renderer.as_ref()))
     }
 
-    fn highlight_code(&self, code: &str, language: Option<&str>) -> String {
-        let syntax = match language {
-            Some(lang) => self
-                .syntax_set
-                .find_syntax_by_token(lang)
-                .or_else(|| self.syntax_set.find_syntax_by_extension(lang)),
-            None => None,
+    // Swap shortcode calls for placeholders, so their HTML can be put back once
+    // the markdown is rendered instead of being parsed as markdown
+    fn expand_shortcodes<'c>(
+        &self,
+        content: &'c str,
+        file: &str,
+        first_line: usize,
+    ) -> Result<(String, Vec<Shortcode<'c>>), Error> {
+        let segments = shortcode::parse(content).map_err(|e| Error::Shortcode {
+            file: file.to_string(),
+            line: first_line + content[..e.offset].matches('\n').count(),
+            message: e.message,
+        })?;
+        let mut output = String::with_capacity(content.len());
+        let mut calls = Vec::new();
+
+        for segment in segments {
+            match segment {
+                Segment::Text(text) => output.push_str(text),
+                Segment::Escaped(text) => output.push_str(&text),
+                Segment::Shortcode(call) => {
+                    output.push_str(&shortcode_placeholder(calls.len()));
+                    calls.push(call);
+                }
+            }
         }
-        .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text());
 
-        let mut html_generator = ClassedHTMLGenerator::new_with_class_style(
-            syntax,
-            &self.syntax_set,
-            ClassStyle::Spaced,
-        );
+        Ok((output, calls))
+    }
+
+    // Render the calls `expand_shortcodes` held out, in placeholder order
+    fn render_shortcodes(
+        &self,
+        content: &str,
+        calls: Vec<Shortcode>,
+        file: &str,
+        first_line: usize,
+        state: &mut DocumentState,
+    ) -> Result<Vec<String>, Error> {
+        let line_at = |offset: usize| first_line + content[..offset].matches('\n').count();
+
+        let mut rendered = Vec::with_capacity(calls.len());
+        for call in calls {
+            let body_html = match call.body {
+                Some(body) => {
+                    let offset = body.as_ptr() as usize - content.as_ptr() as usize;
+                    let source = Source {
+                        file,
+                        line: line_at(offset),
+                    };
+                    Some(self.render_with_ids(body, &source, state)?.html)
+                }
+                None => None,
+            };
+
+            let html = self
+                .components
+                .render(&call, body_html)
+                .map_err(|message| Error::Shortcode {
+                    file: file.to_string(),
+                    line: line_at(call.start),
+                    message,
+                })?;
+            rendered.push(html);
+        }
+
+        Ok(rendered)
+    }
+
+    fn highlight_code(&self, code: &str, fence: &CodeFence) -> String {
+        let language = fence.language.as_deref();
+        let (syntax, syntax_set) = language
+            .and_then(|lang| {
+                let lang = self.language_aliases.get(lang).map_or(lang, String::as_str);
+                [&self.site_syntaxes, &self.syntax_set]
+                    .into_iter()
+                    .find_map(|set| {
+                        set.find_syntax_by_token(lang)
+                            .or_else(|| set.find_syntax_by_extension(lang))
+                            .map(|syntax| (syntax, set))
+                    })
+            })
+            .unwrap_or_else(|| (self.syntax_set.find_syntax_plain_text(), &self.syntax_set));
+
+        let mut html_generator =
+            ClassedHTMLGenerator::new_with_class_style(syntax, syntax_set, ClassStyle::Spaced);
 
         for line in LinesWithEndings::from(code) {
             let _ = html_generator.parse_html_for_line_which_includes_newline(line);
         }
 
-        format!(
+        let mut highlighted = html_generator.finalize();
+        if fence.linenos || !fence.hl_lines.is_empty() {
+            highlighted = split_lines(&highlighted)
+                .into_iter()
+                .enumerate()
+                .map(|(i, line)| {
+                    let number = i + 1;
+                    let class = if fence.hl_lines.iter().any(|r| r.contains(&number)) {
+                        "line hl"
+                    } else {
+                        "line"
+                    };
+                    let lineno = if fence.linenos {
+                        format!("<span class=\"ln\">{}</span>", number)
+                    } else {
+                        String::new()
+                    };
+                    // Keep the newline outside so lines can be styled as blocks
+                    let (line, newline) = match line.strip_suffix('\n') {
+                        Some(line) => (line, "\n"),
+                        None => (line.as_str(), ""),
+                    };
+                    format!(
+                        "<span class=\"{}\">{}{}</span>{}",
+                        class, lineno, line, newline
+                    )
+                })
+                .collect();
+        }
+
+        let pre = format!(
             "<pre class=\"highlight\"><code class=\"language-{}\">{}</code></pre>",
             language.unwrap_or("text"),
-            html_generator.finalize()
-        )
+            highlighted
+        );
+
+        match &fence.title {
+            Some(title) => format!(
+                "<figure class=\"code-block\"><figcaption class=\"highlight-title\">{}</figcaption>{}</figure>",
+                escape_xml(title),
+                pre
+            ),
+            None => pre,
+        }
+    }
+}
+
+// Whether any of `definitions` includes, embeds or pushes a syntax that isn't
+// one of them, which only linking with the bundled syntaxes can resolve
+fn uses_bundled_syntaxes(definitions: &[SyntaxDefinition]) -> bool {
+    let external = |reference: &ContextReference| match reference {
+        ContextReference::ByScope { scope, .. } => !definitions.iter().any(|d| d.scope == *scope),
+        ContextReference::File { name, .. } => !definitions.iter().any(|d| d.name == *name),
+        _ => false,
+    };
+
+    definitions
+        .iter()
+        .flat_map(|definition| definition.contexts.values())
+        .flat_map(|context| &context.patterns)
+        .any(|pattern| match pattern {
+            Pattern::Include(reference) => external(reference),
+            Pattern::Match(pattern) => {
+                let targets = match &pattern.operation {
+                    MatchOperation::Push(targets) | MatchOperation::Set(targets) => {
+                        targets.as_slice()
+                    }
+                    _ => &[],
+                };
+                targets.iter().chain(&pattern.with_prototype).any(external)
+            }
+        })
+}
+
+fn diagram_figure(language: &str, svg: String, fence: &CodeFence) -> String {
+    let caption = fence
+        .title
+        .as_ref()
+        .map(|title| format!("<figcaption>{}</figcaption>", escape_xml(title)))
+        .unwrap_or_default();
+    format!(
+        "<figure class=\"diagram diagram-{}\">{}{}</figure>",
+        language, svg, caption
+    )
+}
+
+// The info string of a fenced code block, e.g. `rust title="main.rs" linenos hl_lines="3-5"`
+#[derive(Debug, Default, PartialEq)]
+struct CodeFence {
+    language: Option<String>,
+    title: Option<String>,
+    linenos: bool,
+    hl_lines: Vec<RangeInclusive<usize>>,
+}
+
+impl CodeFence {
+    fn parse(info: &str) -> Self {
+        let mut fence = CodeFence::default();
+        let mut tokens = fence_tokens(info).into_iter();
+
+        // The language comes first, unless the fence only has attributes
+        if let Some(first) = tokens.next() {
+            if first.contains('=') || first == "linenos" {
+                fence.apply(&first);
+            } else {
+                fence.language = Some(first);
+            }
+        }
+        for token in tokens {
+            fence.apply(&token);
+        }
+        fence
+    }
+
+    fn apply(&mut self, token: &str) {
+        let (key, value) = token.split_once('=').unwrap_or((token, "true"));
+        match key {
+            "title" => self.title = Some(value.to_string()),
+            "linenos" => self.linenos = value != "false",
+            // "3-5", "1,4" or "1 4-6"
+            "hl_lines" => {
+                self.hl_lines = value
+                    .split([',', ' '])
+                    .filter_map(|part| {
+                        let (start, end) = part.split_once('-').unwrap_or((part, part));
+                        Some(start.trim().parse().ok()?..=end.trim().parse().ok()?)
+                    })
+                    .collect();
+            }
+            // Unknown attributes are left for other tools
+            _ => {}
+        }
+    }
+}
+
+// Split an info string on whitespace, keeping quoted values together and unquoting them
+fn fence_tokens(info: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+
+    for c in info.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '"' | '\'') => quote = Some(c),
+            (None, c) if c.is_whitespace() => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            (None, c) => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+// Split syntect's highlighted HTML into one string per source line. Spans can
+// cover several lines (e.g. block comments), so they are closed at the end of
+// each line and reopened on the next to keep every line balanced.
+fn split_lines(html: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut open: Vec<&str> = Vec::new();
+    let mut line = String::new();
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("</span>") {
+            open.pop();
+            line.push_str("</span>");
+            rest = after;
+        } else if rest.starts_with("<span") {
+            let end = rest.find('>').map_or(rest.len(), |i| i + 1);
+            open.push(&rest[..end]);
+            line.push_str(&rest[..end]);
+            rest = &rest[end..];
+        } else {
+            let c = rest.chars().next().unwrap_or_default();
+            line.push(c);
+            rest = &rest[c.len_utf8()..];
+
+            if c == '\n' {
+                // Move the newline after the closing tags so it stays with this line
+                line.pop();
+                line.push_str(&"</span>".repeat(open.len()));
+                line.push('\n');
+                lines.push(std::mem::take(&mut line));
+                line.extend(open.iter().copied());
+            }
+        }
+    }
+
+    // Drop the reopened (and closing) spans left after the final newline
+    let mut in_tag = false;
+    let has_text = line.chars().any(|c| match c {
+        '<' => {
+            in_tag = true;
+            false
+        }
+        '>' => {
+            in_tag = false;
+            false
+        }
+        _ => !in_tag,
+    });
+    if has_text || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+// Stylesheet for the classes `highlight_code` emits, generated from the
+// configured syntax themes. None when no theme is configured.
+pub fn syntax_css(config: &Config) -> Result<Option<String>, Error> {
+    let Some(light) = &config.markdown.syntax_theme else {
+        return Ok(None);
+    };
+
+    let themes = ThemeSet::load_defaults();
+    let mut css = theme_css(&load_theme(config, &themes, light)?)?;
+
+    if let Some(dark) = &config.markdown.syntax_theme_dark {
+        css.push_str("\n@media (prefers-color-scheme: dark) {\n");
+        css.push_str(&theme_css(&load_theme(config, &themes, dark)?)?);
+        css.push_str("}\n");
+    }
+
+    Ok(Some(css))
+}
+
+// A bundled theme by name, or a .tmTheme file relative to the site directory
+fn load_theme(config: &Config, themes: &ThemeSet, name: &str) -> Result<Theme, Error> {
+    if name.ends_with(".tmTheme") {
+        let path = config.site_dir.join(name);
+        return ThemeSet::get_theme(&path)
+            .map_err(|e| Error::SyntaxTheme(format!("Failed to load {}: {}", path.display(), e)));
+    }
+
+    themes.themes.get(name).cloned().ok_or_else(|| {
+        let bundled: Vec<&str> = themes.themes.keys().map(String::as_str).collect();
+        Error::SyntaxTheme(format!(
+            "Unknown theme `{}`. Use a .tmTheme file or one of: {}",
+            name,
+            bundled.join(", ")
+        ))
+    })
+}
+
+fn theme_css(theme: &Theme) -> Result<String, Error> {
+    let css = css_for_theme_with_class_style(theme, ClassStyle::Spaced)
+        .map_err(|e| Error::SyntaxTheme(e.to_string()))?;
+    // syntect puts the theme's base colours on `.code`; our blocks are `pre.highlight`
+    Ok(css.replacen(".code {", "pre.highlight {", 1))
+}
+
+// Plain text that markdown leaves untouched
+const MORE_MARKER: &str = "<!-- more -->";
+
+// Number of leading events that make up the summary: up to a `<!-- more -->`
+// block, or through the first paragraph outside any list, quote or callout
+fn summary_end(events: &[Event]) -> Option<usize> {
+    if let Some(marker) = events
+        .iter()
+        .position(|e| matches!(e, Event::Html(html) if html.trim() == MORE_MARKER))
+    {
+        return Some(marker);
+    }
+
+    let mut depth = 0usize;
+    for (i, event) in events.iter().enumerate() {
+        match event {
+            Event::End(TagEnd::Paragraph) if depth == 1 && !is_toc_marker(&events[..i]) => {
+                return Some(i + 1)
+            }
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth = depth.saturating_sub(1),
+            // Callouts are opened and closed with raw HTML rather than tags
+            Event::Html(html) => {
+                depth += html.matches("<aside").count();
+                depth = depth.saturating_sub(html.matches("</aside>").count());
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// Whether the paragraph ending after `events` is just a `[toc]` marker
+fn is_toc_marker(events: &[Event]) -> bool {
+    let start = events
+        .iter()
+        .rposition(|e| matches!(e, Event::Start(Tag::Paragraph)))
+        .map_or(0, |i| i + 1);
+    // pulldown-cmark splits the brackets into text events of their own
+    let mut text = String::new();
+    for event in &events[start..] {
+        match event {
+            Event::Text(t) => text.push_str(t),
+            _ => return false,
+        }
+    }
+    text == "[toc]" || text == "[TOC]"
+}
+
+fn shortcode_placeholder(index: usize) -> String {
+    format!("termvshortcode{}end", index)
+}
+
+// The text of rendered HTML, for headings a shortcode is part of
+fn html_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn alert_kind(kind: BlockQuoteKind) -> &'static str {
+    match kind {
+        BlockQuoteKind::Note => "note",
+        BlockQuoteKind::Tip => "tip",
+        BlockQuoteKind::Important => "important",
+        BlockQuoteKind::Warning => "warning",
+        BlockQuoteKind::Caution => "caution",
+    }
+}
+
+// Opening markup of a callout box; the title defaults to the capitalised kind
+fn admonition_open(kind: &str, title: Option<&str>) -> String {
+    let title = match title {
+        Some(title) => title.to_string(),
+        None => {
+            let mut chars = kind.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        }
+    };
+
+    format!(
+        "<aside class=\"admonition {}\">\n<p class=\"admonition-title\">{}</p>\n",
+        kind,
+        escape_xml(&title)
+    )
+}
+
+// Rewrite `:::kind [title]` ... `:::` blocks into the same markup as GFM alerts.
+// The blank lines around the body let it be parsed as markdown.
+fn expand_fenced_admonitions(content: &str) -> Cow<'_, str> {
+    if !content.contains(":::") {
+        return Cow::Borrowed(content);
+    }
+
+    let mut output = String::with_capacity(content.len());
+    let mut code_fence: Option<&str> = None;
+    let mut open_blocks = 0;
+
+    for line in content.lines() {
+        let trimmed = line.trim();
+
+        // Leave anything inside code blocks alone
+        if let Some(marker) = code_fence {
+            if trimmed.starts_with(marker) {
+                code_fence = None;
+            }
+        } else if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            code_fence = Some(&trimmed[..3]);
+        } else if let Some(rest) = trimmed.strip_prefix(":::") {
+            let rest = rest.trim();
+            if rest.is_empty() && open_blocks > 0 {
+                open_blocks -= 1;
+                output.push_str("\n</aside>\n\n");
+                continue;
+            } else if !rest.is_empty() {
+                let (kind, title) = match rest.split_once(char::is_whitespace) {
+                    Some((kind, title)) => (kind, Some(title.trim())),
+                    None => (rest, None),
+                };
+                open_blocks += 1;
+                output.push_str(&admonition_open(&slugify(kind), title));
+                output.push('\n');
+                continue;
+            }
+        }
+
+        output.push_str(line);
+        output.push('\n');
+    }
+
+    for _ in 0..open_blocks {
+        output.push_str("\n</aside>\n");
+    }
+
+    Cow::Owned(output)
+}
+
+fn nest_headings(headings: Vec<TocEntry>) -> Vec<TocEntry> {
+    fn insert(entries: &mut Vec<TocEntry>, entry: TocEntry) {
+        match entries.last_mut() {
+            Some(last) if entry.level > last.level => insert(&mut last.children, entry),
+            _ => entries.push(entry),
+        }
+    }
+
+    let mut toc = Vec::new();
+    for heading in headings {
+        insert(&mut toc, heading);
+    }
+    toc
+}
+
+pub fn toc_html(toc: &[TocEntry]) -> String {
+    fn list(entries: &[TocEntry], html: &mut String) {
+        html.push_str("<ul>");
+        for entry in entries {
+            html.push_str(&format!(
+                "<li><a href=\"#{}\">{}</a>",
+                entry.id,
+                escape_xml(&entry.text)
+            ));
+            if !entry.children.is_empty() {
+                list(&entry.children, html);
+            }
+            html.push_str("</li>");
+        }
+        html.push_str("</ul>");
     }
+
+    let mut html = String::from("<nav class=\"toc\">");
+    list(toc, &mut html);
+    html.push_str("</nav>");
+    html
 }
 
 impl Default for MarkdownProcessor {
@@ -117,13 +1086,19 @@ impl Default for MarkdownProcessor {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tempfile::TempDir;
+
+    const SOURCE: Source = Source {
+        file: "posts/test.md",
+        line: 1,
+    };
 
     #[test]
     fn test_basic_markdown() {
         let processor = MarkdownProcessor::new();
         let input = "# Hello\n\nThis is a test";
-        let output = processor.render(input);
-        assert!(output.contains("<h1>Hello</h1>"));
+        let output = processor.render(input).unwrap();
+        assert!(output.contains("<h1 id=\"hello\">Hello</h1>"));
         assert!(output.contains("<p>This is a test</p>"));
     }
 
@@ -131,7 +1106,7 @@ mod tests {
     fn test_code_highlighting() {
         let processor = MarkdownProcessor::new();
         let input = "```rust\nfn main() {\n    println!(\"Hello\");\n}\n```";
-        let output = processor.render(input);
+        let output = processor.render(input).unwrap();
         assert!(output.contains("highlight"));
         assert!(output.contains("language-rust"));
         assert!(output.contains("println!"));
@@ -150,27 +1125,484 @@ def hello():
 ```
 
 More text"#;
-        let output = processor.render(input);
-        assert!(output.contains("<h1>"));
+        let output = processor.render(input).unwrap();
+        assert!(output.contains("<h1 id="));
         assert!(output.contains("language-python"));
         assert!(output.contains("<p>Some text</p>"));
         assert!(output.contains("<p>More text</p>"));
     }
 
+    #[test]
+    fn test_code_fence_attributes() {
+        let fence = CodeFence::parse("rust title=\"src/main.rs\" linenos hl_lines=\"1 3-4\"");
+        assert_eq!(fence.language.as_deref(), Some("rust"));
+        assert_eq!(fence.title.as_deref(), Some("src/main.rs"));
+        assert!(fence.linenos);
+        assert_eq!(fence.hl_lines, vec![1..=1, 3..=4]);
+
+        assert_eq!(CodeFence::parse("linenos").language, None);
+        assert_eq!(CodeFence::parse("").language, None);
+    }
+
+    #[test]
+    fn test_code_block_lines_and_title() {
+        let processor = MarkdownProcessor::new();
+        let input =
+            "```rust title=\"main.rs\" linenos hl_lines=\"2\"\n/* a\nb */\nfn main() {}\n```";
+        let output = processor.render(input).unwrap();
+
+        assert!(output.starts_with(
+            "<figure class=\"code-block\"><figcaption class=\"highlight-title\">main.rs</figcaption><pre class=\"highlight\">"
+        ));
+        assert!(output.contains("<span class=\"line\"><span class=\"ln\">1</span>"));
+        assert!(output.contains("<span class=\"line hl\"><span class=\"ln\">2</span>"));
+        assert!(output.contains("<span class=\"ln\">3</span>"));
+        assert!(!output.contains("<span class=\"ln\">4</span>"));
+
+        // Every line has balanced spans, including the comment spanning lines 1-2
+        for line in output.split("<span class=\"line").skip(1) {
+            let line = line.split("\n").next().unwrap();
+            assert!(line.matches("<span").count() <= line.matches("</span>").count() + 1);
+        }
+    }
+
+    #[test]
+    fn test_split_lines_rebalances_spans() {
+        let html = "<span class=\"comment\">/* a\nb */</span>\n<span class=\"k\">fn</span>\n";
+        assert_eq!(
+            split_lines(html),
+            vec![
+                "<span class=\"comment\">/* a</span>\n",
+                "<span class=\"comment\">b */</span>\n",
+                "<span class=\"k\">fn</span>\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_syntax_css() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = crate::tests::create_test_config(&temp_dir);
+        assert!(syntax_css(&config).unwrap().is_none());
+
+        config.markdown.syntax_theme = Some("InspiredGitHub".to_string());
+        config.markdown.syntax_theme_dark = Some("base16-ocean.dark".to_string());
+        let css = syntax_css(&config).unwrap().unwrap();
+        assert!(css.contains("pre.highlight {"));
+        assert!(css.contains(".comment"));
+        assert!(css.contains("@media (prefers-color-scheme: dark) {"));
+
+        config.markdown.syntax_theme = Some("no-such-theme".to_string());
+        let err = syntax_css(&config).unwrap_err().to_string();
+        assert!(err.contains("Unknown theme `no-such-theme`"));
+        assert!(err.contains("base16-ocean.dark"));
+
+        config.markdown.syntax_theme = Some("themes/missing.tmTheme".to_string());
+        assert!(syntax_css(&config).is_err());
+    }
+
+    #[test]
+    fn test_custom_syntaxes_and_aliases() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = crate::tests::create_test_config(&temp_dir);
+        let syntaxes_dir = config.syntaxes_dir();
+        std::fs::create_dir_all(&syntaxes_dir).unwrap();
+        std::fs::write(
+            syntaxes_dir.join("Widget.sublime-syntax"),
+            "%YAML 1.2\n---\nname: Widget\nfile_extensions: [widget]\nscope: source.widget\ncontexts:\n  main:\n    - match: '\\bwidget\\b'\n      scope: keyword.control.widget\n",
+        )
+        .unwrap();
+        config
+            .markdown
+            .language_aliases
+            .insert("wdg".to_string(), "widget".to_string());
+
+        let processor = MarkdownProcessor::from_config(&config).unwrap();
+        for lang in ["widget", "wdg"] {
+            let output = processor
+                .render(&format!("```{}\nwidget foo\n```", lang))
+                .unwrap();
+            assert!(output.contains("<span class=\"keyword control widget\">widget</span>"));
+            assert!(output.contains(&format!("language-{}", lang)));
+        }
+
+        // Bundled syntaxes are still available
+        let output = processor.render("```rust\nfn main() {}\n```").unwrap();
+        assert!(output.contains("storage type function rust"));
+    }
+
+    #[test]
+    fn test_syntaxes_using_bundled_ones() {
+        let definition = |name: &str, body: &str| {
+            let yaml = format!(
+                "%YAML 1.2\n---\nname: {}\nscope: source.{}\ncontexts:\n  main:\n{}",
+                name,
+                name.to_lowercase(),
+                body
+            );
+            SyntaxDefinition::load_from_str(&yaml, true, None).unwrap()
+        };
+
+        let standalone = definition("Widget", "    - match: 'w'\n      scope: keyword.widget\n");
+        let own = definition("Gadget", "    - include: scope:source.widget\n");
+        assert!(!uses_bundled_syntaxes(&[standalone.clone(), own.clone()]));
+        assert!(uses_bundled_syntaxes(&[own]));
+
+        let embeds = definition(
+            "Doc",
+            "    - match: '```rust'\n      embed: scope:source.rust\n      escape: '```'\n",
+        );
+        assert!(uses_bundled_syntaxes(&[standalone.clone(), embeds]));
+        let pushes = definition(
+            "Tpl",
+            "    - match: '<'\n      push: Packages/HTML/HTML.sublime-syntax\n",
+        );
+        assert!(uses_bundled_syntaxes(&[standalone, pushes]));
+    }
+
+    fn with_math() -> MarkdownProcessor {
+        let mut processor = MarkdownProcessor::new();
+        processor.options.insert(Options::ENABLE_MATH);
+        processor
+    }
+
+    #[test]
+    fn test_math_is_opt_in() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = crate::tests::create_test_config(&temp_dir);
+        let input = "It costs $5,$10 in `$HOME`, or $HOME/$USER";
+
+        let output = MarkdownProcessor::from_config(&config)
+            .unwrap()
+            .render(input)
+            .unwrap();
+        assert!(output.contains("It costs $5,$10 in <code>$HOME</code>, or $HOME/$USER"));
+        assert!(!output.contains("<math"));
+
+        config.markdown.math = true;
+        let output = MarkdownProcessor::from_config(&config)
+            .unwrap()
+            .render("Euler: $e^{i\\pi}$")
+            .unwrap();
+        assert!(output.contains("<math"));
+    }
+
+    #[test]
+    fn test_math() {
+        let processor = with_math();
+        let output = processor
+            .render("Euler: $e^{i\\pi} + 1 = 0$\n\n$$\n\\frac{1}{2}\n$$")
+            .unwrap();
+
+        assert!(output.contains("<p>Euler: <math xmlns=\"http://www.w3.org/1998/Math/MathML\"><semantics><mrow><msup><mi>e</mi>"));
+        assert!(output.contains("<math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"block\"><semantics><mrow><mfrac>"));
+        assert!(!output.contains("$"));
+    }
+
+    #[test]
+    fn test_unsupported_math_is_left_as_tex() {
+        let processor = with_math();
+        let output = processor
+            .render("Half: $\\tfrac12$, $\\lvert x \\rvert$ and $\\mathscr{L} < 1$")
+            .unwrap();
+
+        assert!(output.contains("Half: <math"));
+        assert!(output.contains("<mo>|</mo>"));
+        assert!(output.contains("<span class=\"math inline\">\\(\\mathscr{L} &lt; 1\\)</span>"));
+    }
+
+    #[test]
+    fn test_math_error_points_at_line() {
+        let processor = with_math();
+        let source = Source {
+            file: "posts/math.md",
+            line: 5,
+        };
+        let err = processor
+            .render_document("Fine: $x^2$\n\nBroken: $\\frac{a}$", &source)
+            .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Math error in posts/math.md:7: `\\frac` is missing an argument in `$\\frac{a}$`"
+        );
+    }
+
+    struct Reversed;
+
+    impl DiagramRenderer for Reversed {
+        fn render(&self, source: &str) -> Result<String, String> {
+            if source.contains("bad") {
+                return Err("syntax error".to_string());
+            }
+            Ok(format!(
+                "<svg>{}</svg>",
+                source.trim().chars().rev().collect::<String>()
+            ))
+        }
+    }
+
+    #[test]
+    fn test_diagram_renderer() {
+        let mut processor = MarkdownProcessor::new();
+        processor.register_diagram("rev", Box::new(Reversed));
+
+        let output = processor
+            .render("```rev title=\"Flow\"\nabc\n```\n\n```rust\nabc\n```")
+            .unwrap();
+        assert!(output.contains(
+            "<figure class=\"diagram diagram-rev\"><svg>cba</svg><figcaption>Flow</figcaption></figure>"
+        ));
+        assert!(output.contains("language-rust"));
+
+        let err = processor
+            .render_document("Intro\n\n```rev\nbad\n```", &SOURCE)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Diagram error in posts/test.md:4: syntax error"
+        );
+    }
+
+    #[test]
+    fn test_diagrams_from_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = crate::tests::create_test_config(&temp_dir);
+        config
+            .markdown
+            .diagrams
+            .insert("svg".to_string(), "cat".to_string());
+
+        let processor = MarkdownProcessor::from_config(&config).unwrap();
+        let output = processor.render("```svg\n<svg><g/></svg>\n```").unwrap();
+        assert!(output.contains("<figure class=\"diagram diagram-svg\"><svg><g/></svg></figure>"));
+        assert!(config.site_dir.join(CACHE_DIR).join("diagrams").exists());
+    }
+
     #[test]
     fn test_unknown_language() {
         let processor = MarkdownProcessor::new();
         let input = "```unknown-lang\nsome code\n```";
-        let output = processor.render(input);
+        let output = processor.render(input).unwrap();
         assert!(output.contains("language-unknown-lang"));
         assert!(output.contains("some code"));
     }
 
+    #[test]
+    fn test_heading_ids_and_toc() {
+        let processor = MarkdownProcessor::new();
+        let input = "# Intro\n\n## Setup `cargo`\n\n### Details\n\n## Setup `cargo`\n\n# Intro";
+        let rendered = processor.render_document(input, &SOURCE).unwrap();
+
+        assert!(rendered.html.contains("<h1 id=\"intro\">Intro</h1>"));
+        assert!(rendered
+            .html
+            .contains("<h2 id=\"setup-cargo\">Setup <code>cargo</code></h2>"));
+        assert!(rendered.html.contains("<h2 id=\"setup-cargo-1\">"));
+        assert!(rendered.html.contains("<h1 id=\"intro-1\">"));
+
+        assert_eq!(rendered.toc.len(), 2);
+        let intro = &rendered.toc[0];
+        assert_eq!(intro.id, "intro");
+        assert_eq!(intro.children.len(), 2);
+        assert_eq!(intro.children[0].text, "Setup cargo");
+        assert_eq!(intro.children[0].children[0].id, "details");
+        assert_eq!(intro.children[0].children[0].level, 3);
+    }
+
+    #[test]
+    fn test_explicit_heading_ids() {
+        let processor = MarkdownProcessor::new();
+        let input = "## Setup\n\n## Install {#setup}\n\n## Setup";
+        let rendered = processor.render_document(input, &SOURCE).unwrap();
+
+        assert!(rendered.html.contains("<h2 id=\"setup-1\">Setup</h2>"));
+        assert!(rendered.html.contains("<h2 id=\"setup\">Install</h2>"));
+        assert!(rendered.html.contains("<h2 id=\"setup-2\">Setup</h2>"));
+
+        let input = "## One {#intro}\n\ntext\n\n## Two {#intro}";
+        let err = processor.render_document(input, &SOURCE).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Heading error in posts/test.md:5: the id `intro` is used by more than one heading"
+        );
+    }
+
+    #[test]
+    fn test_toc_marker() {
+        let processor = MarkdownProcessor::new();
+        let input = "[toc]\n\n## First\n\n## Second & Third";
+        let output = processor.render(input).unwrap();
+
+        assert!(output.starts_with("<nav class=\"toc\"><ul><li><a href=\"#first\">First</a></li>"));
+        assert!(output.contains("<a href=\"#second-third\">Second &amp; Third</a>"));
+        assert!(!output.contains("[toc]"));
+    }
+
+    #[test]
+    fn test_summary_more_marker() {
+        let processor = MarkdownProcessor::new();
+        let input = "# Title\n\nFirst *para*.\n\n- a list\n\n<!-- more -->\n\nThe rest.";
+        let rendered = processor.render_document(input, &SOURCE).unwrap();
+
+        assert_eq!(
+            rendered.summary,
+            "<h1>Title</h1>\n<p>First <em>para</em>.</p>\n<ul>\n<li>a list</li>\n</ul>"
+        );
+        assert!(rendered.html.contains("<h1 id=\"title\">Title</h1>"));
+        assert!(rendered.html.contains("<p>The rest.</p>"));
+    }
+
+    #[test]
+    fn test_summary_first_paragraph() {
+        let processor = MarkdownProcessor::new();
+        let input = "[toc]\n\n## Intro\n\n> [!NOTE]\n> Not this.\n\n- Nor this\n\nBut this.\n\nNot this either.";
+        let rendered = processor.render_document(input, &SOURCE).unwrap();
+
+        assert!(rendered.summary.starts_with("<h2>Intro</h2>"));
+        assert!(rendered.summary.ends_with("<p>But this.</p>"));
+        assert!(!rendered.summary.contains("toc"));
+        assert!(!rendered.summary.contains("either"));
+
+        let rendered = processor
+            .render_document("```\ncode\n```", &SOURCE)
+            .unwrap();
+        assert_eq!(rendered.summary, "");
+    }
+
+    #[test]
+    fn test_gfm_alert() {
+        let processor = MarkdownProcessor::new();
+        let output = processor
+            .render("> [!NOTE]\n> Remember to **save**.\n\n> Plain quote")
+            .unwrap();
+
+        assert!(output.contains(
+            "<aside class=\"admonition note\">\n<p class=\"admonition-title\">Note</p>\n<p>Remember to <strong>save</strong>.</p>\n</aside>"
+        ));
+        assert!(output.contains("<blockquote>\n<p>Plain quote</p>\n</blockquote>"));
+    }
+
+    #[test]
+    fn test_fenced_admonition() {
+        let processor = MarkdownProcessor::new();
+        let input = ":::warning Mind the gap\nThis is *dangerous*.\n:::\n\n:::tip\nA tip\n:::";
+        let output = processor.render(input).unwrap();
+
+        assert!(output.contains("<aside class=\"admonition warning\">"));
+        assert!(output.contains("<p class=\"admonition-title\">Mind the gap</p>"));
+        assert!(output.contains("<p>This is <em>dangerous</em>.</p>\n</aside>"));
+        assert!(output.contains("<p class=\"admonition-title\">Tip</p>"));
+        assert!(!output.contains(":::"));
+    }
+
+    #[test]
+    fn test_fenced_admonition_in_code_block_is_untouched() {
+        let processor = MarkdownProcessor::new();
+        let output = processor.render("```\n:::note\n```\n").unwrap();
+
+        assert!(!output.contains("admonition"));
+        assert!(output.contains(":::note"));
+    }
+
+    fn with_components(components: &[(&str, &str)]) -> (TempDir, MarkdownProcessor) {
+        let temp_dir = TempDir::new().unwrap();
+        for (name, template) in components {
+            std::fs::write(temp_dir.path().join(name), template).unwrap();
+        }
+        let processor = MarkdownProcessor::with_components(temp_dir.path()).unwrap();
+        (temp_dir, processor)
+    }
+
+    #[test]
+    fn test_shortcodes() {
+        let (_dir, processor) = with_components(&[
+            (
+                "youtube.html",
+                "<iframe src=\"https://www.youtube.com/embed/{{ id }}?start={{ start }}\"></iframe>",
+            ),
+            ("note.html", "<div class=\"note\">{{ body | safe }}</div>"),
+        ]);
+        let input = "Watch:\n\n{{< youtube id=\"abc\" start=30 />}}\n\n{{< note >}}\nSome *emphasis*\n{{< /note >}}\n\n`{{</* youtube id=\"abc\" */>}}`";
+        let output = processor.render(input).unwrap();
+
+        assert!(
+            output.contains("<iframe src=\"https://www.youtube.com/embed/abc?start=30\"></iframe>")
+        );
+        assert!(!output.contains("<p><iframe"));
+        assert!(output.contains("<div class=\"note\"><p>Some <em>emphasis</em></p>\n</div>"));
+        assert!(output.contains("<code>{{&lt; youtube id=\"abc\" &gt;}}</code>"));
+    }
+
+    #[test]
+    fn test_shortcode_headings() {
+        let (_dir, processor) = with_components(&[
+            ("version.html", "<span class=\"version\">v{{ n }}</span>"),
+            ("note.html", "<div class=\"note\">{{ body | safe }}</div>"),
+        ]);
+        let input =
+            "## Setup {{< version n=2 />}}\n\n## Setup\n\n{{< note >}}\n## Setup\n{{< /note >}}";
+        let rendered = processor.render_document(input, &SOURCE).unwrap();
+
+        assert_eq!(rendered.toc[0].text, "Setup v2");
+        assert_eq!(rendered.toc[0].id, "setup-v2");
+        assert!(rendered
+            .html
+            .contains("<h2 id=\"setup-v2\">Setup <span class=\"version\">v2</span></h2>"));
+        assert!(!rendered.html.contains("termvshortcode"));
+
+        // The heading in the note's body still gets an id of its own
+        let ids: Vec<&str> = rendered
+            .html
+            .match_indices(" id=\"")
+            .map(|(i, _)| {
+                let rest = &rendered.html[i + 5..];
+                &rest[..rest.find('"').unwrap()]
+            })
+            .collect();
+        assert_eq!(ids.len(), 3);
+        assert!(ids
+            .iter()
+            .all(|id| ids.iter().filter(|other| *other == id).count() == 1));
+    }
+
+    #[test]
+    fn test_shortcode_errors_point_at_file_and_line() {
+        let (_dir, processor) =
+            with_components(&[("figure.html", "<img src=\"{{ src }}\" alt=\"{{ alt }}\">")]);
+        let source = Source {
+            file: "posts/hello.md",
+            line: 6,
+        };
+
+        let err = processor
+            .render_document("Intro\n\n{{< video id=\"x\" />}}", &source)
+            .unwrap_err();
+        match err {
+            Error::Shortcode {
+                file,
+                line,
+                message,
+            } => {
+                assert_eq!(file, "posts/hello.md");
+                assert_eq!(line, 8);
+                assert!(message.contains("unknown shortcode `video`"));
+            }
+            other => panic!("unexpected error: {other}"),
+        }
+
+        let err = processor
+            .render_document("{{< figure src=\"a.png\" />}}", &source)
+            .unwrap_err();
+        assert!(err.to_string().contains("posts/hello.md:6"));
+        assert!(err.to_string().contains("alt"));
+    }
+
     #[test]
     fn test_inline_code() {
         let processor = MarkdownProcessor::new();
         let input = "This is `inline code`";
-        let output = processor.render(input);
+        let output = processor.render(input).unwrap();
         assert!(output.contains("<code>inline code</code>"));
     }
 }
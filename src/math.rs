@@ -0,0 +1,1037 @@
+use crate::feed::escape_xml;
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum MathError {
+    // Broken TeX, e.g. a missing argument or an unclosed group
+    Malformed(String),
+    // A command or environment the converter doesn't know
+    Unsupported(String),
+}
+
+// Converts the LaTeX math used in posts into MathML, so formulas render
+// without any JavaScript. Covers the common subset: scripts, fractions,
+// roots, Greek letters and symbols, fonts, accents, \left/\right and
+// matrix-style environments.
+pub fn to_mathml(tex: &str, display: bool) -> Result<String, MathError> {
+    let mut parser = Parser {
+        src: tex,
+        pos: 0,
+        display,
+        unsupported: false,
+        depth: 0,
+    };
+    let body = parser.parse_top().map_err(|message| {
+        if parser.unsupported {
+            MathError::Unsupported(message)
+        } else {
+            MathError::Malformed(message)
+        }
+    })?;
+
+    Ok(format!(
+        "<math xmlns=\"http://www.w3.org/1998/Math/MathML\"{}><semantics><mrow>{}</mrow><annotation encoding=\"application/x-tex\">{}</annotation></semantics></math>",
+        if display { " display=\"block\"" } else { "" },
+        body,
+        escape_xml(tex.trim())
+    ))
+}
+
+// Math the converter doesn't support, left as TeX for a client-side renderer
+// such as KaTeX or MathJax to pick up
+pub fn raw_tex(tex: &str, display: bool) -> String {
+    let (class, open, close) = if display {
+        ("math display", "\\[", "\\]")
+    } else {
+        ("math inline", "\\(", "\\)")
+    };
+    format!(
+        "<span class=\"{}\">{}{}{}</span>",
+        class,
+        open,
+        escape_xml(tex.trim()),
+        close
+    )
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Token<'a> {
+    Char(char),
+    Number(&'a str),
+    // Name without the backslash; a single character for `\,`, `\{`, `\\` etc.
+    Command(&'a str),
+    Open,
+    Close,
+    Sup,
+    Sub,
+    Align,
+    Eof,
+}
+
+impl fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Char(c) => write!(f, "`{}`", c),
+            Token::Number(n) => write!(f, "`{}`", n),
+            Token::Command(name) => write!(f, "`\\{}`", name),
+            Token::Open => write!(f, "`{{`"),
+            Token::Close => write!(f, "`}}`"),
+            Token::Sup => write!(f, "`^`"),
+            Token::Sub => write!(f, "`_`"),
+            Token::Align => write!(f, "`&`"),
+            Token::Eof => write!(f, "end of expression"),
+        }
+    }
+}
+
+// A parsed element, and whether scripts on it go above and below in display math
+struct Atom {
+    mathml: String,
+    limits: bool,
+}
+
+impl Atom {
+    fn new(mathml: String) -> Self {
+        Self {
+            mathml,
+            limits: false,
+        }
+    }
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    display: bool,
+    // Set when parsing stopped at a command or environment it doesn't know
+    unsupported: bool,
+    // Groups and arguments currently open, so deep nesting can't overflow the stack
+    depth: usize,
+}
+
+// Deeper than any real formula; past it the TeX is left for a client-side renderer
+const MAX_DEPTH: usize = 64;
+
+impl<'a> Parser<'a> {
+    fn lex(&self) -> (Token<'a>, usize) {
+        let rest = &self.src[self.pos..];
+        let trimmed = rest.trim_start();
+        let start = self.pos + (rest.len() - trimmed.len());
+
+        let mut chars = trimmed.chars();
+        let Some(c) = chars.next() else {
+            return (Token::Eof, start);
+        };
+
+        match c {
+            '{' => (Token::Open, start + 1),
+            '}' => (Token::Close, start + 1),
+            '^' => (Token::Sup, start + 1),
+            '_' => (Token::Sub, start + 1),
+            '&' => (Token::Align, start + 1),
+            '\\' => {
+                let name = &trimmed[1..];
+                let len = name
+                    .find(|c: char| !c.is_ascii_alphabetic())
+                    .unwrap_or(name.len());
+                let len = match len {
+                    // Control symbol such as `\,` or `\\`
+                    0 => name.chars().next().map_or(0, char::len_utf8),
+                    len => len,
+                };
+                (Token::Command(&name[..len]), start + 1 + len)
+            }
+            c if c.is_ascii_digit() => {
+                let len = trimmed
+                    .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                    .unwrap_or(trimmed.len());
+                (Token::Number(&trimmed[..len]), start + len)
+            }
+            c => (Token::Char(c), start + c.len_utf8()),
+        }
+    }
+
+    fn peek(&self) -> Token<'a> {
+        self.lex().0
+    }
+
+    fn next(&mut self) -> Token<'a> {
+        let (token, pos) = self.lex();
+        self.pos = pos;
+        token
+    }
+
+    fn parse_top(&mut self) -> Result<String, String> {
+        let row = self.parse_row()?;
+        match self.next() {
+            Token::Eof => Ok(row),
+            Token::Close => Err("unmatched `}`".to_string()),
+            Token::Align => Err("`&` is only allowed inside an environment".to_string()),
+            Token::Command("right") => Err("`\\right` without a matching `\\left`".to_string()),
+            Token::Command("end") => Err("`\\end` without a matching `\\begin`".to_string()),
+            Token::Command("\\") => Err("`\\\\` is only allowed inside an environment".to_string()),
+            token => Err(format!("unexpected {}", token)),
+        }
+    }
+
+    // Elements up to the end of the current group, cell or \left...\right pair
+    fn parse_row(&mut self) -> Result<String, String> {
+        let mut row = String::new();
+        loop {
+            match self.peek() {
+                Token::Eof
+                | Token::Close
+                | Token::Align
+                | Token::Command("right" | "end" | "\\") => return Ok(row),
+                _ => row.push_str(&self.parse_scripted()?),
+            }
+        }
+    }
+
+    fn parse_scripted(&mut self) -> Result<String, String> {
+        let mut base = self.parse_atom()?;
+        if let Token::Command(cmd @ ("limits" | "nolimits")) = self.peek() {
+            self.next();
+            base.limits = cmd == "limits";
+        }
+
+        let mut sub = None;
+        let mut sup = None;
+        loop {
+            match self.peek() {
+                Token::Sub => {
+                    self.next();
+                    if sub.is_some() {
+                        return Err("double subscript".to_string());
+                    }
+                    sub = Some(self.script_argument("subscript")?);
+                }
+                Token::Sup => {
+                    self.next();
+                    if sup.is_some() {
+                        return Err("double superscript".to_string());
+                    }
+                    sup = Some(self.script_argument("superscript")?);
+                }
+                _ => break,
+            }
+        }
+
+        let limits = base.limits && self.display;
+        let base = base.mathml;
+        Ok(match (sub, sup) {
+            (None, None) => base,
+            (Some(sub), None) if limits => format!("<munder>{}{}</munder>", base, sub),
+            (Some(sub), None) => format!("<msub>{}{}</msub>", base, sub),
+            (None, Some(sup)) if limits => format!("<mover>{}{}</mover>", base, sup),
+            (None, Some(sup)) => format!("<msup>{}{}</msup>", base, sup),
+            (Some(sub), Some(sup)) if limits => {
+                format!("<munderover>{}{}{}</munderover>", base, sub, sup)
+            }
+            (Some(sub), Some(sup)) => format!("<msubsup>{}{}{}</msubsup>", base, sub, sup),
+        })
+    }
+
+    fn script_argument(&mut self, what: &str) -> Result<String, String> {
+        match self.peek() {
+            Token::Eof
+            | Token::Close
+            | Token::Align
+            | Token::Sub
+            | Token::Sup
+            | Token::Command("right" | "end" | "\\") => Err(format!("missing {}", what)),
+            _ => Ok(self.single()?.mathml),
+        }
+    }
+
+    // The single element or group a command like \frac applies to
+    fn argument(&mut self, command: &str) -> Result<String, String> {
+        match self.peek() {
+            Token::Eof
+            | Token::Close
+            | Token::Align
+            | Token::Sub
+            | Token::Sup
+            | Token::Command("right" | "end" | "\\") => {
+                Err(format!("`\\{}` is missing an argument", command))
+            }
+            _ => Ok(self.single()?.mathml),
+        }
+    }
+
+    // An argument without braces is a single token, and so a single digit:
+    // `\frac12` is a half and `x^23` is x squared followed by 3, as in TeX
+    fn single(&mut self) -> Result<Atom, String> {
+        if let (Token::Number(n), end) = self.lex() {
+            if n.len() > 1 {
+                self.pos = end - n.len() + 1;
+                return Ok(Atom::new(format!("<mn>{}</mn>", &n[..1])));
+            }
+        }
+        self.parse_atom()
+    }
+
+    // Parse `src` on its own, e.g. the index of `\sqrt[n]`
+    fn nested(&mut self, src: &str) -> Result<String, String> {
+        let mut parser = Parser {
+            src,
+            pos: 0,
+            display: self.display,
+            unsupported: false,
+            depth: self.depth,
+        };
+        let result = parser.parse_top();
+        self.unsupported |= parser.unsupported;
+        result
+    }
+
+    // The raw text of a `{...}` argument, for \text and environment names
+    fn raw_group(&mut self, command: &str) -> Result<&'a str, String> {
+        let rest = &self.src[self.pos..];
+        let trimmed = rest.trim_start();
+        let Some(body) = trimmed.strip_prefix('{') else {
+            return Err(format!("`\\{}` is missing an argument", command));
+        };
+
+        let mut depth = 0;
+        for (i, c) in body.char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' if depth == 0 => {
+                    self.pos += rest.len() - trimmed.len() + 1 + i + 1;
+                    return Ok(&body[..i]);
+                }
+                '}' => depth -= 1,
+                _ => {}
+            }
+        }
+        Err(format!("`\\{}` is missing its closing `}}`", command))
+    }
+
+    fn parse_atom(&mut self) -> Result<Atom, String> {
+        if self.depth == MAX_DEPTH {
+            self.unsupported = true;
+            return Err(format!("nested more than {} levels deep", MAX_DEPTH));
+        }
+        self.depth += 1;
+        let atom = self.atom();
+        self.depth -= 1;
+        atom
+    }
+
+    fn atom(&mut self) -> Result<Atom, String> {
+        match self.peek() {
+            // A script with nothing before it, like `^2`
+            Token::Sub | Token::Sup => return Ok(Atom::new("<mrow></mrow>".to_string())),
+            _ => {}
+        }
+
+        match self.next() {
+            Token::Number(n) => Ok(Atom::new(format!("<mn>{}</mn>", n))),
+            Token::Char(c) if c.is_alphabetic() => Ok(Atom::new(format!("<mi>{}</mi>", c))),
+            Token::Char('\'') => Ok(Atom::new("<mo>′</mo>".to_string())),
+            Token::Char('-') => Ok(Atom::new("<mo>−</mo>".to_string())),
+            Token::Char(c) => Ok(Atom::new(format!(
+                "<mo>{}</mo>",
+                escape_xml(&c.to_string())
+            ))),
+            Token::Open => {
+                let row = self.parse_row()?;
+                match self.next() {
+                    Token::Close => Ok(Atom::new(format!("<mrow>{}</mrow>", row))),
+                    _ => Err("missing closing `}`".to_string()),
+                }
+            }
+            Token::Command(name) => self.command(name),
+            Token::Close => Err("unexpected `}`".to_string()),
+            Token::Eof => Err("unexpected end of expression".to_string()),
+            token => Err(format!("unexpected {}", token)),
+        }
+    }
+
+    fn command(&mut self, name: &'a str) -> Result<Atom, String> {
+        if let Some(symbol) = identifier(name) {
+            return Ok(Atom::new(symbol));
+        }
+        if let Some(symbol) = operator(name) {
+            return Ok(Atom::new(format!("<mo>{}</mo>", symbol)));
+        }
+        if let Some((symbol, limits)) = big_operator(name) {
+            return Ok(Atom {
+                mathml: format!("<mo>{}</mo>", symbol),
+                limits,
+            });
+        }
+        if let Some(limits) = function(name) {
+            return Ok(Atom {
+                mathml: format!("<mi>{}</mi>", name),
+                limits,
+            });
+        }
+        if let Some(variant) = font(name) {
+            let arg = self.argument(name)?;
+            return Ok(Atom::new(
+                arg.replace("<mi>", &format!("<mi mathvariant=\"{}\">", variant))
+                    .replace("<mn>", &format!("<mn mathvariant=\"{}\">", variant)),
+            ));
+        }
+        if let Some(accent) = accent(name) {
+            let arg = self.argument(name)?;
+            return Ok(Atom::new(format!(
+                "<mover accent=\"true\">{}<mo>{}</mo></mover>",
+                arg, accent
+            )));
+        }
+        if let Some(width) = space(name) {
+            return Ok(Atom::new(format!("<mspace width=\"{}\"/>", width)));
+        }
+
+        match name {
+            "frac" | "dfrac" | "tfrac" | "cfrac" => {
+                let numerator = self.argument(name)?;
+                let denominator = self.argument(name)?;
+                Ok(Atom::new(format!(
+                    "<mfrac>{}{}</mfrac>",
+                    numerator, denominator
+                )))
+            }
+            "binom" => {
+                let n = self.argument(name)?;
+                let k = self.argument(name)?;
+                Ok(Atom::new(format!(
+                    "<mrow><mo>(</mo><mfrac linethickness=\"0\">{}{}</mfrac><mo>)</mo></mrow>",
+                    n, k
+                )))
+            }
+            "sqrt" => {
+                let index = self.optional_index()?;
+                let radicand = self.argument(name)?;
+                Ok(Atom::new(match index {
+                    Some(index) => format!("<mroot>{}<mrow>{}</mrow></mroot>", radicand, index),
+                    None => format!("<msqrt>{}</msqrt>", radicand),
+                }))
+            }
+            "text" | "textrm" | "textit" | "textbf" | "mbox" => {
+                let text = self.raw_group(name)?;
+                Ok(Atom::new(format!("<mtext>{}</mtext>", escape_xml(text))))
+            }
+            "operatorname" => {
+                let text = self.raw_group(name)?;
+                Ok(Atom::new(format!("<mi>{}</mi>", escape_xml(text.trim()))))
+            }
+            "overbrace" | "underbrace" => {
+                let arg = self.argument(name)?;
+                let mathml = if name == "overbrace" {
+                    format!("<mover>{}<mo>⏞</mo></mover>", arg)
+                } else {
+                    format!("<munder>{}<mo>⏟</mo></munder>", arg)
+                };
+                Ok(Atom {
+                    mathml,
+                    limits: true,
+                })
+            }
+            "underline" => {
+                let arg = self.argument(name)?;
+                Ok(Atom::new(format!(
+                    "<munder accentunder=\"true\">{}<mo>_</mo></munder>",
+                    arg
+                )))
+            }
+            "overset" | "stackrel" | "underset" => {
+                let script = self.argument(name)?;
+                let base = self.argument(name)?;
+                let tag = if name == "underset" {
+                    "munder"
+                } else {
+                    "mover"
+                };
+                Ok(Atom::new(format!("<{0}>{1}{2}</{0}>", tag, base, script)))
+            }
+            "substack" => {
+                let body = self.raw_group(name)?;
+                let mut table = String::from("<mtable>");
+                for row in body.split("\\\\").filter(|row| !row.trim().is_empty()) {
+                    table.push_str(&format!("<mtr><mtd>{}</mtd></mtr>", self.nested(row)?));
+                }
+                table.push_str("</mtable>");
+                Ok(Atom::new(table))
+            }
+            // Sized delimiters: \big( \Bigl[ \biggr| ...
+            "big" | "Big" | "bigg" | "Bigg" | "bigl" | "Bigl" | "biggl" | "Biggl" | "bigr"
+            | "Bigr" | "biggr" | "Biggr" | "bigm" | "Bigm" | "biggm" | "Biggm" => {
+                let size = match name.trim_end_matches(['l', 'r', 'm']) {
+                    "big" => "1.2em",
+                    "Big" => "1.623em",
+                    "bigg" => "2.047em",
+                    _ => "2.470em",
+                };
+                let delim = self.delimiter(name)?;
+                Ok(Atom::new(format!(
+                    "<mo fence=\"true\" stretchy=\"true\" minsize=\"{0}\" maxsize=\"{0}\">{1}</mo>",
+                    size, delim
+                )))
+            }
+            // A switch colouring the rest of the group
+            "color" => {
+                let color = self.raw_group(name)?;
+                let rest = self.parse_row()?;
+                Ok(Atom::new(format!(
+                    "<mstyle mathcolor=\"{}\">{}</mstyle>",
+                    escape_xml(color.trim()),
+                    rest
+                )))
+            }
+            "textcolor" => {
+                let color = self.raw_group(name)?;
+                let arg = self.argument(name)?;
+                Ok(Atom::new(format!(
+                    "<mstyle mathcolor=\"{}\">{}</mstyle>",
+                    escape_xml(color.trim()),
+                    arg
+                )))
+            }
+            // An equation number, `\tag*` without the parentheses
+            "tag" => {
+                let starred = self.peek() == Token::Char('*');
+                if starred {
+                    self.next();
+                }
+                let label = self.raw_group(name)?;
+                let label = if starred {
+                    label.to_string()
+                } else {
+                    format!("({})", label)
+                };
+                Ok(Atom::new(format!(
+                    "<mspace width=\"2em\"/><mtext>{}</mtext>",
+                    escape_xml(&label)
+                )))
+            }
+            "left" => self.left_right(),
+            "begin" => {
+                let env = self.raw_group(name)?;
+                self.environment(env)
+            }
+            // Style switches don't change the MathML
+            "displaystyle" | "textstyle" | "limits" | "nolimits" => Ok(Atom::new(String::new())),
+            "right" => Err("`\\right` without a matching `\\left`".to_string()),
+            "end" => Err("`\\end` without a matching `\\begin`".to_string()),
+            "" => Err("stray `\\` at the end of the expression".to_string()),
+            _ => {
+                self.unsupported = true;
+                Err(format!("unknown command `\\{}`", name))
+            }
+        }
+    }
+
+    // The `[n]` of `\sqrt[n]{x}`
+    fn optional_index(&mut self) -> Result<Option<String>, String> {
+        let rest = &self.src[self.pos..];
+        let trimmed = rest.trim_start();
+        let Some(body) = trimmed.strip_prefix('[') else {
+            return Ok(None);
+        };
+        let end = body
+            .find(']')
+            .ok_or_else(|| "`\\sqrt[` is missing its closing `]`".to_string())?;
+
+        let index = self.nested(&body[..end])?;
+        self.pos += rest.len() - trimmed.len() + 1 + end + 1;
+        Ok(Some(index))
+    }
+
+    fn delimiter(&mut self, command: &str) -> Result<&'static str, String> {
+        let delim = match self.next() {
+            Token::Char('.') => "",
+            Token::Char('(') => "(",
+            Token::Char(')') => ")",
+            Token::Char('[') => "[",
+            Token::Char(']') => "]",
+            Token::Char('|') => "|",
+            Token::Char('/') => "/",
+            Token::Char('<') => "⟨",
+            Token::Char('>') => "⟩",
+            Token::Command("{" | "lbrace") => "{",
+            Token::Command("}" | "rbrace") => "}",
+            Token::Command("|" | "Vert" | "lVert" | "rVert") => "‖",
+            Token::Command("vert" | "lvert" | "rvert") => "|",
+            Token::Command("langle") => "⟨",
+            Token::Command("rangle") => "⟩",
+            Token::Command("lfloor") => "⌊",
+            Token::Command("rfloor") => "⌋",
+            Token::Command("lceil") => "⌈",
+            Token::Command("rceil") => "⌉",
+            _ => return Err(format!("`\\{}` must be followed by a delimiter", command)),
+        };
+        Ok(delim)
+    }
+
+    fn left_right(&mut self) -> Result<Atom, String> {
+        let open = self.delimiter("left")?;
+        let body = self.parse_row()?;
+        if self.next() != Token::Command("right") {
+            return Err("`\\left` without a matching `\\right`".to_string());
+        }
+        let close = self.delimiter("right")?;
+
+        Ok(Atom::new(format!(
+            "<mrow>{}{}{}</mrow>",
+            fence(open),
+            body,
+            fence(close)
+        )))
+    }
+
+    fn environment(&mut self, name: &str) -> Result<Atom, String> {
+        let (open, close, columnalign) = match name {
+            "matrix" => ("", "", None),
+            "pmatrix" => ("(", ")", None),
+            "bmatrix" => ("[", "]", None),
+            "Bmatrix" => ("{", "}", None),
+            "vmatrix" => ("|", "|", None),
+            "Vmatrix" => ("‖", "‖", None),
+            "cases" => ("{", "", Some("left left")),
+            "aligned" | "align" | "align*" => ("", "", Some("right left")),
+            _ => {
+                self.unsupported = true;
+                return Err(format!("unknown environment `{}`", name));
+            }
+        };
+
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        let mut cells = Vec::new();
+        loop {
+            cells.push(self.parse_row()?);
+            match self.next() {
+                Token::Align => {}
+                Token::Command("\\") => rows.push(std::mem::take(&mut cells)),
+                Token::Command("end") => {
+                    let end = self.raw_group("end")?;
+                    if end != name {
+                        return Err(format!("`\\begin{{{}}}` ended by `\\end{{{}}}`", name, end));
+                    }
+                    rows.push(cells);
+                    break;
+                }
+                Token::Eof => {
+                    return Err(format!(
+                        "`\\begin{{{}}}` is missing its `\\end{{{}}}`",
+                        name, name
+                    ))
+                }
+                Token::Close => return Err("unexpected `}`".to_string()),
+                _ => return Err("`\\right` without a matching `\\left`".to_string()),
+            }
+        }
+
+        // A trailing `\\` before `\end` doesn't start a new row
+        if rows.len() > 1 && rows.last().is_some_and(|r| r.len() == 1 && r[0].is_empty()) {
+            rows.pop();
+        }
+
+        let mut table = match columnalign {
+            Some(align) => format!("<mtable columnalign=\"{}\">", align),
+            None => "<mtable>".to_string(),
+        };
+        for row in rows {
+            table.push_str("<mtr>");
+            for cell in row {
+                table.push_str(&format!("<mtd>{}</mtd>", cell));
+            }
+            table.push_str("</mtr>");
+        }
+        table.push_str("</mtable>");
+
+        Ok(Atom::new(if open.is_empty() && close.is_empty() {
+            table
+        } else {
+            format!("<mrow>{}{}{}</mrow>", fence(open), table, fence(close))
+        }))
+    }
+}
+
+fn fence(delim: &str) -> String {
+    if delim.is_empty() {
+        String::new()
+    } else {
+        format!("<mo fence=\"true\" stretchy=\"true\">{}</mo>", delim)
+    }
+}
+
+fn identifier(name: &str) -> Option<String> {
+    let lower = match name {
+        "alpha" => "α",
+        "beta" => "β",
+        "gamma" => "γ",
+        "delta" => "δ",
+        "epsilon" => "ϵ",
+        "varepsilon" => "ε",
+        "zeta" => "ζ",
+        "eta" => "η",
+        "theta" => "θ",
+        "vartheta" => "ϑ",
+        "iota" => "ι",
+        "kappa" => "κ",
+        "lambda" => "λ",
+        "mu" => "μ",
+        "nu" => "ν",
+        "xi" => "ξ",
+        "omicron" => "ο",
+        "pi" => "π",
+        "varpi" => "ϖ",
+        "rho" => "ρ",
+        "varrho" => "ϱ",
+        "sigma" => "σ",
+        "varsigma" => "ς",
+        "tau" => "τ",
+        "upsilon" => "υ",
+        "phi" => "ϕ",
+        "varphi" => "φ",
+        "chi" => "χ",
+        "psi" => "ψ",
+        "omega" => "ω",
+        "infty" => "∞",
+        "partial" => "∂",
+        "nabla" => "∇",
+        "emptyset" | "varnothing" => "∅",
+        "hbar" => "ℏ",
+        "ell" => "ℓ",
+        "aleph" => "ℵ",
+        "Re" => "ℜ",
+        "Im" => "ℑ",
+        _ => "",
+    };
+    if !lower.is_empty() {
+        return Some(format!("<mi>{}</mi>", lower));
+    }
+
+    // Upright, as capital Greek letters are conventionally set
+    let upper = match name {
+        "Gamma" => "Γ",
+        "Delta" => "Δ",
+        "Theta" => "Θ",
+        "Lambda" => "Λ",
+        "Xi" => "Ξ",
+        "Pi" => "Π",
+        "Sigma" => "Σ",
+        "Upsilon" => "Υ",
+        "Phi" => "Φ",
+        "Psi" => "Ψ",
+        "Omega" => "Ω",
+        _ => return None,
+    };
+    Some(format!("<mi mathvariant=\"normal\">{}</mi>", upper))
+}
+
+fn operator(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "pm" => "±",
+        "mp" => "∓",
+        "times" => "×",
+        "div" => "÷",
+        "cdot" | "cdotp" => "⋅",
+        "ast" => "∗",
+        "star" => "⋆",
+        "circ" => "∘",
+        "bullet" => "∙",
+        "oplus" => "⊕",
+        "otimes" => "⊗",
+        "leq" | "le" => "≤",
+        "geq" | "ge" => "≥",
+        "neq" | "ne" => "≠",
+        "approx" => "≈",
+        "equiv" => "≡",
+        "sim" => "∼",
+        "simeq" => "≃",
+        "cong" => "≅",
+        "propto" => "∝",
+        "ll" => "≪",
+        "gg" => "≫",
+        "in" => "∈",
+        "notin" => "∉",
+        "ni" => "∋",
+        "subset" => "⊂",
+        "supset" => "⊃",
+        "subseteq" => "⊆",
+        "supseteq" => "⊇",
+        "cup" => "∪",
+        "cap" => "∩",
+        "setminus" => "∖",
+        "forall" => "∀",
+        "exists" => "∃",
+        "neg" | "lnot" => "¬",
+        "land" | "wedge" => "∧",
+        "lor" | "vee" => "∨",
+        "to" | "rightarrow" => "→",
+        "leftarrow" | "gets" => "←",
+        "leftrightarrow" => "↔",
+        "Rightarrow" => "⇒",
+        "Leftarrow" => "⇐",
+        "Leftrightarrow" | "iff" => "⇔",
+        "implies" => "⟹",
+        "mapsto" => "↦",
+        "uparrow" => "↑",
+        "downarrow" => "↓",
+        "ldots" | "dots" => "…",
+        "cdots" => "⋯",
+        "vdots" => "⋮",
+        "ddots" => "⋱",
+        "prime" => "′",
+        "langle" => "⟨",
+        "rangle" => "⟩",
+        "lfloor" => "⌊",
+        "rfloor" => "⌋",
+        "lceil" => "⌈",
+        "rceil" => "⌉",
+        "mid" => "∣",
+        "parallel" => "∥",
+        "perp" => "⊥",
+        "angle" => "∠",
+        "triangle" => "△",
+        "colon" => ":",
+        "vert" | "lvert" | "rvert" => "|",
+        "Vert" | "lVert" | "rVert" | "|" => "‖",
+        "{" | "lbrace" => "{",
+        "}" | "rbrace" => "}",
+        "bmod" | "mod" => "mod",
+        "%" => "%",
+        "$" => "$",
+        "#" => "#",
+        "&" => "&amp;",
+        "_" => "_",
+        _ => return None,
+    })
+}
+
+// Symbol, and whether its scripts become limits in display math
+fn big_operator(name: &str) -> Option<(&'static str, bool)> {
+    Some(match name {
+        "sum" => ("∑", true),
+        "prod" => ("∏", true),
+        "coprod" => ("∐", true),
+        "bigcup" => ("⋃", true),
+        "bigcap" => ("⋂", true),
+        "bigoplus" => ("⨁", true),
+        "bigotimes" => ("⨂", true),
+        "int" => ("∫", false),
+        "iint" => ("∬", false),
+        "iiint" => ("∭", false),
+        "oint" => ("∮", false),
+        _ => return None,
+    })
+}
+
+// Named functions, set upright; Some(true) for those taking limits like \lim
+fn function(name: &str) -> Option<bool> {
+    match name {
+        "lim" | "liminf" | "limsup" | "max" | "min" | "sup" | "inf" | "det" | "gcd" | "Pr"
+        | "argmax" | "argmin" => Some(true),
+        "sin" | "cos" | "tan" | "sec" | "csc" | "cot" | "sinh" | "cosh" | "tanh" | "arcsin"
+        | "arccos" | "arctan" | "log" | "ln" | "lg" | "exp" | "arg" | "deg" | "dim" | "ker"
+        | "hom" => Some(false),
+        _ => None,
+    }
+}
+
+fn font(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "mathbf" => "bold",
+        "mathrm" => "normal",
+        "mathit" => "italic",
+        "mathbb" => "double-struck",
+        "mathcal" => "script",
+        "mathfrak" => "fraktur",
+        "mathsf" => "sans-serif",
+        "mathtt" => "monospace",
+        "boldsymbol" => "bold-italic",
+        _ => return None,
+    })
+}
+
+fn accent(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "hat" | "widehat" => "^",
+        "bar" => "¯",
+        "overline" => "‾",
+        "vec" => "→",
+        "tilde" | "widetilde" => "˜",
+        "dot" => "˙",
+        "ddot" => "¨",
+        _ => return None,
+    })
+}
+
+fn space(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "," => "0.1667em",
+        ":" | ">" => "0.2222em",
+        ";" => "0.2778em",
+        "!" => "-0.1667em",
+        " " => "0.25em",
+        "quad" => "1em",
+        "qquad" => "2em",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body(tex: &str) -> String {
+        let mathml = to_mathml(tex, false).unwrap();
+        let start = mathml.find("<semantics><mrow>").unwrap() + "<semantics><mrow>".len();
+        let end = mathml.find("</mrow><annotation").unwrap();
+        mathml[start..end].to_string()
+    }
+
+    #[test]
+    fn test_scripts_and_fractions() {
+        assert_eq!(
+            body("x^2 + y_i"),
+            "<msup><mi>x</mi><mn>2</mn></msup><mo>+</mo><msub><mi>y</mi><mi>i</mi></msub>"
+        );
+        assert_eq!(
+            body(r"\frac{a}{2b}"),
+            "<mfrac><mrow><mi>a</mi></mrow><mrow><mn>2</mn><mi>b</mi></mrow></mfrac>"
+        );
+        assert_eq!(
+            body(r"\sqrt[3]{x}"),
+            "<mroot><mrow><mi>x</mi></mrow><mrow><mn>3</mn></mrow></mroot>"
+        );
+        assert_eq!(
+            body(r"e^{i\pi}"),
+            "<msup><mi>e</mi><mrow><mi>i</mi><mi>π</mi></mrow></msup>"
+        );
+    }
+
+    #[test]
+    fn test_limits_in_display_math() {
+        let tex = r"\sum_{i=1}^n i";
+        assert!(body(tex).starts_with("<msubsup><mo>∑</mo>"));
+
+        let display = to_mathml(tex, true).unwrap();
+        assert!(display.contains(" display=\"block\""));
+        assert!(display.contains("<munderover><mo>∑</mo>"));
+        assert!(display
+            .contains("<annotation encoding=\"application/x-tex\">\\sum_{i=1}^n i</annotation>"));
+    }
+
+    #[test]
+    fn test_fonts_text_and_fences() {
+        assert_eq!(
+            body(r"\mathbb{R}"),
+            "<mrow><mi mathvariant=\"double-struck\">R</mi></mrow>"
+        );
+        assert_eq!(
+            body(r"\text{if } x < 0"),
+            "<mtext>if </mtext><mi>x</mi><mo>&lt;</mo><mn>0</mn>"
+        );
+        assert_eq!(
+            body(r"\left( x \right)"),
+            "<mrow><mo fence=\"true\" stretchy=\"true\">(</mo><mi>x</mi><mo fence=\"true\" stretchy=\"true\">)</mo></mrow>"
+        );
+    }
+
+    #[test]
+    fn test_environments() {
+        let matrix = body(r"\begin{pmatrix} a & b \\ c & d \\ \end{pmatrix}");
+        assert_eq!(
+            matrix,
+            "<mrow><mo fence=\"true\" stretchy=\"true\">(</mo><mtable><mtr><mtd><mi>a</mi></mtd><mtd><mi>b</mi></mtd></mtr><mtr><mtd><mi>c</mi></mtd><mtd><mi>d</mi></mtd></mtr></mtable><mo fence=\"true\" stretchy=\"true\">)</mo></mrow>"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = |tex: &str| match to_mathml(tex, false) {
+            Err(MathError::Malformed(message)) => message,
+            other => panic!("expected malformed math, got {:?}", other),
+        };
+        assert_eq!(err(r"\frac{a}"), "`\\frac` is missing an argument");
+        assert_eq!(err("{x"), "missing closing `}`");
+        assert_eq!(err("x}"), "unmatched `}`");
+        assert_eq!(err("x^"), "missing superscript");
+        assert_eq!(err("x^1^2"), "double superscript");
+        assert_eq!(err(r"\left( x"), "`\\left` without a matching `\\right`");
+        assert_eq!(
+            err(r"\begin{matrix} a \end{pmatrix}"),
+            "`\\begin{matrix}` ended by `\\end{pmatrix}`"
+        );
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let unsupported = |message: &str| Err(MathError::Unsupported(message.to_string()));
+        assert_eq!(
+            to_mathml(r"\foo", false),
+            unsupported("unknown command `\\foo`")
+        );
+        assert_eq!(
+            to_mathml(r"\sqrt[\foo]{x}", false),
+            unsupported("unknown command `\\foo`")
+        );
+        assert_eq!(
+            to_mathml(r"\begin{foo}", false),
+            unsupported("unknown environment `foo`")
+        );
+
+        let deep = format!("{}x{}", "{".repeat(50_000), "}".repeat(50_000));
+        assert_eq!(
+            to_mathml(&deep, false),
+            unsupported("nested more than 64 levels deep")
+        );
+        assert_eq!(
+            to_mathml(&r"\frac".repeat(50_000), false),
+            unsupported("nested more than 64 levels deep")
+        );
+
+        assert_eq!(
+            raw_tex(r" a < \foo ", false),
+            "<span class=\"math inline\">\\(a &lt; \\foo\\)</span>"
+        );
+        assert_eq!(
+            raw_tex(r"\foo", true),
+            "<span class=\"math display\">\\[\\foo\\]</span>"
+        );
+    }
+
+    #[test]
+    fn test_bare_token_arguments() {
+        let half = "<mfrac><mn>1</mn><mn>2</mn></mfrac>";
+        assert_eq!(body(r"\frac12"), half);
+        assert_eq!(body(r"\tfrac12"), half);
+        assert_eq!(body(r"\dfrac 1 2"), half);
+        assert_eq!(body(r"\frac ab"), "<mfrac><mi>a</mi><mi>b</mi></mfrac>");
+        assert_eq!(body(r"\sqrt2"), "<msqrt><mn>2</mn></msqrt>");
+        assert_eq!(body("x^23"), "<msup><mi>x</mi><mn>2</mn></msup><mn>3</mn>");
+        assert_eq!(body(r"a_\alpha"), "<msub><mi>a</mi><mi>α</mi></msub>");
+    }
+
+    #[test]
+    fn test_delimiters_stacks_and_styles() {
+        assert_eq!(body(r"\lvert x \rvert"), "<mo>|</mo><mi>x</mi><mo>|</mo>");
+        assert_eq!(
+            body(r"\big( \Bigl[ \biggr\rvert"),
+            "<mo fence=\"true\" stretchy=\"true\" minsize=\"1.2em\" maxsize=\"1.2em\">(</mo><mo fence=\"true\" stretchy=\"true\" minsize=\"1.623em\" maxsize=\"1.623em\">[</mo><mo fence=\"true\" stretchy=\"true\" minsize=\"2.047em\" maxsize=\"2.047em\">|</mo>"
+        );
+        assert_eq!(
+            body(r"\overset{!}{=} \stackrel{def}{=}"),
+            "<mover><mrow><mo>=</mo></mrow><mrow><mo>!</mo></mrow></mover><mover><mrow><mo>=</mo></mrow><mrow><mi>d</mi><mi>e</mi><mi>f</mi></mrow></mover>"
+        );
+        assert_eq!(
+            body(r"\sum_{\substack{i<n \\ j}} 1"),
+            "<msub><mo>∑</mo><mrow><mtable><mtr><mtd><mi>i</mi><mo>&lt;</mo><mi>n</mi></mtd></mtr><mtr><mtd><mi>j</mi></mtd></mtr></mtable></mrow></msub><mn>1</mn>"
+        );
+        assert_eq!(
+            body(r"{\color{red} x} y"),
+            "<mrow><mstyle mathcolor=\"red\"><mi>x</mi></mstyle></mrow><mi>y</mi>"
+        );
+        assert_eq!(
+            body(r"x \tag{1}"),
+            "<mi>x</mi><mspace width=\"2em\"/><mtext>(1)</mtext>"
+        );
+        assert_eq!(body(r"\tag*{A}"), "<mspace width=\"2em\"/><mtext>A</mtext>");
+    }
+}
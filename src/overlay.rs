@@ -0,0 +1,206 @@
+use crate::errors::Error;
+use crate::feed::escape_xml;
+use crate::livereload::{inject_script, LIVE_RELOAD_PATH};
+
+// What went wrong in the last rebuild, shown in the browser by `termv serve`
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildFailure {
+    pub kind: String,
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl BuildFailure {
+    pub fn from_error(error: &Error) -> Self {
+        let message = error_chain(error);
+
+        // Point at the first of several failures, but show them all
+        if let Error::Multiple(errors) = error {
+            if let Some(first) = errors.first() {
+                return Self {
+                    message,
+                    ..Self::from_error(first)
+                };
+            }
+        }
+
+        let (kind, file, line) = match error {
+            Error::Template(_) => (
+                "Template error",
+                quoted(&message),
+                line_from_parse_error(&message),
+            ),
+            Error::Frontmatter { file, line, .. } => {
+                ("Frontmatter error", Some(file.clone()), Some(*line))
+            }
+            Error::Shortcode { file, line, .. } => {
+                ("Shortcode error", Some(file.clone()), Some(*line))
+            }
+            Error::Math { file, line, .. } => ("Math error", Some(file.clone()), Some(*line)),
+            Error::Diagram { file, line, .. } => ("Diagram error", Some(file.clone()), Some(*line)),
+            Error::Heading { file, line, .. } => ("Heading error", Some(file.clone()), Some(*line)),
+            Error::ConfigParse(_) | Error::Toml(_) => ("Configuration error", None, None),
+            Error::Permalink(_) => ("Permalink error", None, None),
+            Error::Io(_) => ("IO error", None, None),
+            _ => ("Build error", None, None),
+        };
+
+        Self {
+            kind: kind.to_string(),
+            file,
+            line,
+            message,
+        }
+    }
+
+    pub fn location(&self) -> Option<String> {
+        match (&self.file, self.line) {
+            (Some(file), Some(line)) => Some(format!("{}:{}", file, line)),
+            (Some(file), None) => Some(file.clone()),
+            _ => None,
+        }
+    }
+}
+
+// Tera nests the useful part of its errors (the failing variable, the parse
+// position) in the source chain, so collect every level
+fn error_chain(error: &Error) -> String {
+    let mut lines = vec![error.to_string()];
+    let mut source = std::error::Error::source(error);
+    while let Some(err) = source {
+        let text = err.to_string();
+        if !lines.iter().any(|l| l.contains(text.trim())) {
+            lines.push(text);
+        }
+        source = err.source();
+    }
+    lines.join("\n")
+}
+
+// First 'single' or "double" quoted string, which is how Tera names the template
+fn quoted(message: &str) -> Option<String> {
+    let start = message.find(['\'', '"'])?;
+    let quote = message[start..].chars().next()?;
+    let rest = &message[start + 1..];
+    let end = rest.find(quote)?;
+    Some(rest[..end].to_string())
+}
+
+// Parse errors point at the failing position as " --> line:column"
+fn line_from_parse_error(message: &str) -> Option<usize> {
+    let idx = message.find("--> ")?;
+    message[idx + 4..]
+        .split(':')
+        .next()
+        .and_then(|line| line.trim().parse().ok())
+}
+
+// Whether a request is for a page (rather than an asset) and so should get
+// the overlay while the build is broken
+pub fn wants_page(path: &str) -> bool {
+    if path.starts_with(LIVE_RELOAD_PATH) {
+        return false;
+    }
+    path.ends_with('/')
+        || path.ends_with(".html")
+        || std::path::Path::new(path).extension().is_none()
+}
+
+pub fn render_overlay(failure: &BuildFailure) -> String {
+    let location = failure
+        .location()
+        .map(|loc| format!("<p class=\"location\">{}</p>", escape_xml(&loc)))
+        .unwrap_or_default();
+
+    let html = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="UTF-8">
+<title>Build failed - termv</title>
+<style>
+  body {{ margin: 0; padding: 2rem; background: #1a1b26; color: #c0caf5; font-family: 'JetBrains Mono', Menlo, monospace; }}
+  h1 {{ color: #f7768e; font-size: 1.5rem; margin-top: 0; }}
+  .location {{ color: #e0af68; }}
+  pre {{ background: #24283b; padding: 1rem; border-left: 4px solid #f7768e; white-space: pre-wrap; overflow-x: auto; }}
+  .hint {{ color: #565f89; }}
+</style>
+</head>
+<body>
+<h1>{}</h1>
+{}
+<pre>{}</pre>
+<p class="hint">Fix the error and save; this page will refresh once the site rebuilds.</p>
+</body>
+</html>"#,
+        escape_xml(&failure.kind),
+        location,
+        escape_xml(&failure.message)
+    );
+
+    // Reload as soon as a rebuild succeeds
+    inject_script(&html)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_template_parse_failure() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("post.html"), "<p>\n{{ foo \n</p>").unwrap();
+
+        let error = Error::Template(
+            tera::Tera::new(&format!("{}/*.html", temp_dir.path().display())).unwrap_err(),
+        );
+        let failure = BuildFailure::from_error(&error);
+
+        assert_eq!(failure.kind, "Template error");
+        assert!(failure.file.unwrap().ends_with("post.html"));
+        assert_eq!(failure.line, Some(3));
+        assert!(failure.message.contains("expected an expression"));
+    }
+
+    #[test]
+    fn test_template_render_failure() {
+        let mut tera = tera::Tera::default();
+        tera.add_raw_template("post.html", "{{ post.missing }}")
+            .unwrap();
+        let error = Error::Template(tera.render("post.html", &tera::Context::new()).unwrap_err());
+        let failure = BuildFailure::from_error(&error);
+
+        assert_eq!(failure.file.as_deref(), Some("post.html"));
+        assert_eq!(failure.line, None);
+        assert!(failure
+            .message
+            .contains("Variable `post.missing` not found"));
+    }
+
+    #[test]
+    fn test_render_overlay() {
+        let failure = BuildFailure::from_error(&Error::Frontmatter {
+            file: "posts/<bad>.md".to_string(),
+            line: 2,
+            message: "missing field `title`".to_string(),
+        });
+        let html = render_overlay(&failure);
+
+        assert!(html.contains("<h1>Frontmatter error</h1>"));
+        assert!(html.contains("posts/&lt;bad&gt;.md"));
+        assert!(html.contains("missing field `title`"));
+        assert!(html.contains(LIVE_RELOAD_PATH));
+    }
+
+    #[test]
+    fn test_wants_page() {
+        assert!(wants_page("/"));
+        assert!(wants_page("/posts/foo/"));
+        assert!(wants_page("/about"));
+        assert!(wants_page("/index.html"));
+        assert!(!wants_page("/css/style.css"));
+        assert!(!wants_page(LIVE_RELOAD_PATH));
+    }
+}
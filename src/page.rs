@@ -0,0 +1,185 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tera::Value;
+
+use crate::errors::Error;
+use crate::frontmatter::{self, Document};
+use crate::markdown::{MarkdownProcessor, Source, TocEntry};
+
+// A standalone page (about, now, uses, ...) rendered outside the post stream
+#[derive(Debug, Serialize)]
+pub struct Page {
+    pub metadata: PageMetadata,
+    // File the page was read from
+    #[serde(skip)]
+    pub source: PathBuf,
+    pub content: String,
+    pub html_content: String,
+    pub toc: Vec<TocEntry>,
+    // Cache keys of its diagrams
+    #[serde(skip)]
+    pub diagrams: Vec<String>,
+    // Site-relative URL the page is published at, e.g. "/about/"
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PageMetadata {
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    // Overrides the URL derived from the file's location in the pages directory
+    #[serde(default, deserialize_with = "validate_path")]
+    pub path: Option<String>,
+    #[serde(default = "default_true")]
+    pub sitemap: bool,
+    // Custom values for templates, as `page.metadata.extra`
+    #[serde(default)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn validate_path<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let path = String::deserialize(deserializer)?;
+    check_url_path(&path).map_err(serde::de::Error::custom)?;
+    Ok(Some(path))
+}
+
+// URL paths become directories under the output directory, so they may only
+// be made of plain names
+pub fn check_url_path(path: &str) -> Result<(), String> {
+    for segment in path.split('/') {
+        if segment == "." || segment == ".." || segment.contains(['\\', ':']) {
+            return Err(format!(
+                "`{}` can't be used in a URL path, only plain names separated by `/` (in `{}`)",
+                segment, path
+            ));
+        }
+    }
+    Ok(())
+}
+
+impl Page {
+    pub fn new_from_path(
+        path: &Path,
+        pages_dir: &Path,
+        md_proc: &MarkdownProcessor,
+    ) -> Result<Self, Error> {
+        let content = fs::read_to_string(path)?;
+        let file_name = path.display().to_string();
+
+        let doc: Document<PageMetadata> = frontmatter::parse(&file_name, &content)?;
+
+        let url = match &doc.metadata.path {
+            Some(custom) => normalize_url(custom),
+            None => url_for_file(path, pages_dir),
+        };
+
+        let source = Source {
+            file: &file_name,
+            line: doc.content_line,
+        };
+        let rendered = md_proc.render_document(&doc.content, &source)?;
+        Ok(Self {
+            html_content: rendered.html,
+            toc: rendered.toc,
+            diagrams: rendered.diagrams,
+            content: doc.content,
+            metadata: doc.metadata,
+            source: path.to_path_buf(),
+            url,
+        })
+    }
+}
+
+fn normalize_url(path: &str) -> String {
+    let trimmed = path.trim().trim_matches('/');
+    if trimmed.is_empty() {
+        "/".to_string()
+    } else {
+        format!("/{}/", trimmed)
+    }
+}
+
+// pages/about.md -> /about/, pages/docs/index.md -> /docs/
+fn url_for_file(path: &Path, pages_dir: &Path) -> String {
+    let relative = path.strip_prefix(pages_dir).unwrap_or(path);
+    let mut segments: Vec<String> = relative
+        .with_extension("")
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+
+    if segments.last().is_some_and(|s| s == "index") {
+        segments.pop();
+    }
+
+    normalize_url(&segments.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_url_for_file() {
+        let pages_dir = Path::new("/site/pages");
+        assert_eq!(
+            url_for_file(&pages_dir.join("about.md"), pages_dir),
+            "/about/"
+        );
+        assert_eq!(
+            url_for_file(&pages_dir.join("docs/setup.md"), pages_dir),
+            "/docs/setup/"
+        );
+        assert_eq!(
+            url_for_file(&pages_dir.join("docs/index.md"), pages_dir),
+            "/docs/"
+        );
+        assert_eq!(normalize_url("uses"), "/uses/");
+        assert_eq!(normalize_url("/"), "/");
+    }
+
+    #[test]
+    fn test_page_from_path() -> Result<(), Error> {
+        let temp_dir = TempDir::new()?;
+        let pages_dir = temp_dir.path().join("pages");
+        fs::create_dir_all(&pages_dir)?;
+
+        let about = pages_dir.join("about.md");
+        fs::write(&about, "---\ntitle: \"About\"\n---\n# About me")?;
+        let page = Page::new_from_path(&about, &pages_dir, &MarkdownProcessor::new())?;
+        assert_eq!(page.url, "/about/");
+        assert!(page
+            .html_content
+            .contains("<h1 id=\"about-me\">About me</h1>"));
+
+        let now = pages_dir.join("now.md");
+        fs::write(
+            &now,
+            "---\ntitle: \"Now\"\npath: \"/what-im-doing\"\n---\nStuff",
+        )?;
+        let page = Page::new_from_path(&now, &pages_dir, &MarkdownProcessor::new())?;
+        assert_eq!(page.url, "/what-im-doing/");
+
+        for path in ["../../x", "/docs/./setup", "C:\\x", "/a/../../b/"] {
+            fs::write(&now, format!("---\ntitle: Now\npath: '{}'\n---\n", path))?;
+            let err = Page::new_from_path(&now, &pages_dir, &MarkdownProcessor::new()).unwrap_err();
+            assert!(
+                err.to_string().contains("can't be used in a URL path"),
+                "{}",
+                err
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+use serde::Serialize;
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Paginator {
+    pub current_page: usize,
+    pub total_pages: usize,
+    pub per_page: usize,
+    pub total_items: usize,
+    pub first_url: String,
+    pub last_url: String,
+    pub previous_url: Option<String>,
+    pub next_url: Option<String>,
+}
+
+pub struct Page<'a, T> {
+    pub items: &'a [T],
+    pub paginator: Paginator,
+}
+
+// URL of the given 1-based page of a listing rooted at `base_url`. The first
+// page lives at the listing root, later pages under `page/<n>/`.
+pub fn page_url(base_url: &str, page: usize) -> String {
+    let base = if base_url.ends_with('/') {
+        base_url.to_string()
+    } else {
+        format!("{}/", base_url)
+    };
+
+    if page <= 1 {
+        base
+    } else {
+        format!("{}page/{}/", base, page)
+    }
+}
+
+// Split `items` into pages of `per_page` entries. `None` or `0` disables
+// pagination and yields a single page holding everything. An empty listing
+// still produces one (empty) page so index pages are always written.
+pub fn paginate<'a, T>(
+    items: &'a [T],
+    per_page: Option<usize>,
+    base_url: &str,
+) -> Vec<Page<'a, T>> {
+    let per_page = match per_page {
+        Some(n) if n > 0 => n,
+        _ => items.len().max(1),
+    };
+
+    let chunks: Vec<&[T]> = if items.is_empty() {
+        vec![items]
+    } else {
+        items.chunks(per_page).collect()
+    };
+    let total_pages = chunks.len();
+
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(idx, chunk)| {
+            let current_page = idx + 1;
+            Page {
+                items: chunk,
+                paginator: Paginator {
+                    current_page,
+                    total_pages,
+                    per_page,
+                    total_items: items.len(),
+                    first_url: page_url(base_url, 1),
+                    last_url: page_url(base_url, total_pages),
+                    previous_url: (current_page > 1).then(|| page_url(base_url, current_page - 1)),
+                    next_url: (current_page < total_pages)
+                        .then(|| page_url(base_url, current_page + 1)),
+                },
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_url() {
+        assert_eq!(page_url("/", 1), "/");
+        assert_eq!(page_url("/", 2), "/page/2/");
+        assert_eq!(page_url("/tags/rust", 3), "/tags/rust/page/3/");
+    }
+
+    #[test]
+    fn test_paginate() {
+        let items: Vec<u32> = (1..=5).collect();
+        let pages = paginate(&items, Some(2), "/");
+
+        assert_eq!(pages.len(), 3);
+        assert_eq!(pages[0].items, &[1, 2]);
+        assert_eq!(pages[2].items, &[5]);
+
+        let first = &pages[0].paginator;
+        assert_eq!(first.current_page, 1);
+        assert_eq!(first.total_pages, 3);
+        assert_eq!(first.total_items, 5);
+        assert_eq!(first.previous_url, None);
+        assert_eq!(first.next_url.as_deref(), Some("/page/2/"));
+
+        let last = &pages[2].paginator;
+        assert_eq!(last.previous_url.as_deref(), Some("/page/2/"));
+        assert_eq!(last.next_url, None);
+        assert_eq!(last.last_url, "/page/3/");
+    }
+
+    #[test]
+    fn test_paginate_disabled_or_empty() {
+        let items: Vec<u32> = (1..=5).collect();
+        let pages = paginate(&items, None, "/");
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].items.len(), 5);
+
+        let empty: Vec<u32> = Vec::new();
+        let pages = paginate(&empty, Some(10), "/");
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].paginator.total_pages, 1);
+        assert!(pages[0].items.is_empty());
+    }
+}
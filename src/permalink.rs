@@ -0,0 +1,167 @@
+use crate::page::check_url_path;
+use crate::post::{slugify, PostMetadata};
+
+// Where posts are published unless `permalink` is set in config.toml
+pub const DEFAULT: &str = "/posts/:slug/";
+
+const TOKENS: [&str; 5] = ["year", "month", "day", "slug", "tag"];
+
+enum Piece<'a> {
+    Text(&'a str),
+    Token(&'a str),
+}
+
+// Split a pattern such as "/:year/:month/:slug/" into text and `:token`s
+fn pieces(pattern: &str) -> Vec<Piece<'_>> {
+    let mut pieces = Vec::new();
+    let mut rest = pattern;
+    while let Some(start) = rest.find(':') {
+        let name_len = rest[start + 1..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len() - start - 1);
+        if name_len == 0 {
+            // A lone colon is part of the path
+            pieces.push(Piece::Text(&rest[..=start]));
+        } else {
+            pieces.push(Piece::Text(&rest[..start]));
+            pieces.push(Piece::Token(&rest[start + 1..start + 1 + name_len]));
+        }
+        rest = &rest[start + 1 + name_len..];
+    }
+    pieces.push(Piece::Text(rest));
+    pieces
+}
+
+pub fn validate(pattern: &str) -> Result<(), String> {
+    if let Some(segment) = pattern.split('/').find(|s| *s == "." || *s == "..") {
+        return Err(format!(
+            "`{}` can't be used in `{}`, posts have to stay inside the output directory",
+            segment, pattern
+        ));
+    }
+    for piece in pieces(pattern) {
+        if let Piece::Token(name) = piece {
+            if !TOKENS.contains(&name) {
+                return Err(format!(
+                    "unknown token `:{}` in `{}`, expected one of {}",
+                    name,
+                    pattern,
+                    TOKENS.map(|t| format!("`:{}`", t)).join(", ")
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+// The site-wide pattern has to tell posts apart
+pub fn validate_site_pattern(pattern: &str) -> Result<(), String> {
+    validate(pattern)?;
+    if !pieces(pattern)
+        .iter()
+        .any(|piece| matches!(piece, Piece::Token("slug")))
+    {
+        return Err(format!(
+            "`{}` has no `:slug`, so every post would share a URL",
+            pattern
+        ));
+    }
+    Ok(())
+}
+
+// Site-relative URL for a post, e.g. "/2024/11/hello/". Dates are taken in the
+// timezone the post's date was pinned to, and `:tag` is its first tag (dropped
+// when it has none). Fails when the result isn't a plain path, e.g. for a slug
+// of "..".
+pub fn expand(pattern: &str, metadata: &PostMetadata) -> Result<String, String> {
+    let date = metadata.date.datetime();
+    let path: String = pieces(pattern)
+        .into_iter()
+        .map(|piece| match piece {
+            Piece::Text(text) => text.to_string(),
+            Piece::Token("year") => date.format("%Y").to_string(),
+            Piece::Token("month") => date.format("%m").to_string(),
+            Piece::Token("day") => date.format("%d").to_string(),
+            Piece::Token("slug") => metadata.slug.clone(),
+            Piece::Token("tag") => metadata
+                .tags
+                .first()
+                .map(|tag| slugify(tag))
+                .unwrap_or_default(),
+            Piece::Token(name) => format!(":{}", name),
+        })
+        .collect();
+
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let url = if segments.is_empty() {
+        "/".to_string()
+    } else {
+        format!("/{}/", segments.join("/"))
+    };
+    check_url_path(&url)?;
+    Ok(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontmatter;
+
+    fn metadata(frontmatter: &str) -> PostMetadata {
+        let raw = format!("---\ntitle: Hi\nslug: hello\n{}\n---\n", frontmatter);
+        frontmatter::parse("post.md", &raw).unwrap().metadata
+    }
+
+    #[test]
+    fn test_expand() {
+        let expand = |pattern: &str, metadata: &PostMetadata| expand(pattern, metadata).unwrap();
+        let post = metadata("date: 2024-03-05\ntags: [\"Rust Lang\", web]");
+        assert_eq!(expand(DEFAULT, &post), "/posts/hello/");
+        assert_eq!(
+            expand("/:year/:month/:day/:slug/", &post),
+            "/2024/03/05/hello/"
+        );
+        assert_eq!(expand(":tag/:slug", &post), "/rust-lang/hello/");
+        assert_eq!(
+            expand("/blog/:year-:month/:slug", &post),
+            "/blog/2024-03/hello/"
+        );
+
+        let untagged = metadata("date: 2024-03-05");
+        assert_eq!(expand("/:tag/:slug/", &untagged), "/hello/");
+        assert_eq!(expand("/", &untagged), "/");
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate_site_pattern("/:year/:month/:slug/").is_ok());
+        assert!(validate("/about/").is_ok());
+        assert!(validate("/a:/b/").is_ok());
+
+        let err = validate("/:yaer/:slug/").unwrap_err();
+        assert!(
+            err.starts_with("unknown token `:yaer` in `/:yaer/:slug/`, expected one of `:year`")
+        );
+        let err = validate_site_pattern("/:year/:month/").unwrap_err();
+        assert!(err.contains("no `:slug`"));
+
+        let err = validate("../../etc").unwrap_err();
+        assert_eq!(
+            err,
+            "`..` can't be used in `../../etc`, posts have to stay inside the output directory"
+        );
+        assert!(validate("/./:slug/").is_err());
+        assert!(validate("/..a/").is_ok());
+    }
+
+    #[test]
+    fn test_expanded_urls_stay_plain_paths() {
+        let post = metadata("date: 2024-03-05");
+        let dotted = PostMetadata {
+            slug: "..".to_string(),
+            ..post
+        };
+        let err = expand("/:slug/", &dotted).unwrap_err();
+        assert!(err.starts_with("`..` can't be used in a URL path"));
+    }
+}
@@ -1,41 +1,95 @@
-use chrono::{Local, NaiveDate};
+use chrono::Utc;
+use chrono_tz::Tz;
 use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
-use yaml_front_matter::{Document, YamlFrontMatter};
+use tera::Value;
 
 use crate::config::Config;
+use crate::date::PostDate;
 use crate::errors::Error;
-use crate::markdown::MarkdownProcessor;
+use crate::frontmatter::{self, Document};
+use crate::markdown::{MarkdownProcessor, Source, TocEntry};
+use crate::permalink;
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Post {
     pub metadata: PostMetadata,
+    // Site-relative URL the post is published at, from its permalink
+    pub url: String,
+    // File the post was read from
+    #[serde(skip)]
+    pub source: PathBuf,
     pub content: String,
     pub html_content: String,
+    // Rendered excerpt for listings and feeds, used when there's no `preview`
+    pub summary: String,
+    pub toc: Vec<TocEntry>,
+    // Cache keys of its diagrams, kept with the cached rendering so the
+    // diagrams stay cached while it is reused
+    #[serde(default)]
+    pub diagrams: Vec<String>,
 }
 
 impl Post {
-    pub fn new_from_path(path: &Path, md_proc: &MarkdownProcessor) -> Result<Self, Error> {
+    // When the post last changed: `updated` if known, otherwise its date
+    pub fn last_modified(&self) -> PostDate {
+        self.metadata.updated.unwrap_or(self.metadata.date)
+    }
+
+    // `timezone` places dates written without an offset, and `permalink` is the
+    // site's pattern for posts that don't set their own
+    pub fn new_from_path(
+        path: &Path,
+        md_proc: &MarkdownProcessor,
+        timezone: Tz,
+        permalink: &str,
+    ) -> Result<Self, Error> {
         let content = fs::read_to_string(path)?;
         let file_name = path.display().to_string();
 
-        let doc: Document<PostMetadata> =
-            YamlFrontMatter::parse(&content).map_err(|e| Error::Frontmatter {
-                file: file_name,
-                message: e.to_string(),
-            })?;
+        let doc: Document<PostMetadata> = frontmatter::parse(&file_name, &content)?;
 
-        let mut post = Self {
-            metadata: doc.metadata,
-            content: doc.content.clone(),
-            html_content: md_proc.render(&doc.content),
+        let source = Source {
+            file: &file_name,
+            line: doc.content_line,
         };
+        let rendered = md_proc.render_document(&doc.content, &source)?;
+
+        let mut metadata = doc.metadata;
+        metadata.date = metadata.date.in_timezone(timezone);
+        metadata.updated = metadata.updated.map(|d| d.in_timezone(timezone));
+        metadata.read_time = calculate_read_time(&doc.content);
+        let url = permalink::expand(
+            metadata.permalink.as_deref().unwrap_or(permalink),
+            &metadata,
+        )
+        .map_err(|message| Error::Permalink(format!("{}: {}", file_name, message)))?;
+
+        Ok(Self {
+            metadata,
+            url,
+            source: path.to_path_buf(),
+            content: doc.content,
+            html_content: rendered.html,
+            summary: rendered.summary,
+            toc: rendered.toc,
+            diagrams: rendered.diagrams,
+        })
+    }
 
-        post.metadata.read_time = calculate_read_time(&doc.content);
+    // A post as an earlier build rendered it (see `BuildCache::rendered_path`)
+    pub fn from_cache(value: Value, path: &Path, timezone: Tz) -> Result<Self, Error> {
+        let mut post: Self = tera::from_value(value).map_err(tera::Error::from)?;
+        post.source = path.to_path_buf();
+        // Plain dates are cached without an offset, like they're written
+        post.metadata.date = post.metadata.date.in_timezone(timezone);
+        post.metadata.updated = post.metadata.updated.map(|d| d.in_timezone(timezone));
         Ok(post)
     }
+
     // Get the assets directory for this post
     pub fn assets_dir(&self, config: &Config) -> PathBuf {
         config
@@ -48,15 +102,14 @@ impl Post {
     pub fn assets_output_dir(&self, config: &Config) -> PathBuf {
         config
             .output_dir()
-            .join("posts")
-            .join(&self.metadata.slug)
+            .join(self.url.trim_matches('/'))
             .join(&config.build.post_assets_dir)
     }
 
     // Process post content to update asset paths
     pub fn process_asset_paths(&mut self, config: &Config) {
         // Update markdown image/video paths to point to the correct output location
-        let post_url = format!("/posts/{}", self.metadata.slug);
+        let post_url = self.url.trim_end_matches('/').to_string();
 
         // Replace relative asset paths with absolute paths
         let content = self.content.replace(
@@ -68,22 +121,27 @@ impl Post {
             &format!("{}/{}", post_url, config.build.post_assets_dir),
         );
 
-        // Also update the HTML content
-        let html_content = self.html_content.replace(
-            &format!("./{}/", config.build.post_assets_dir),
-            &format!("{}/{}/", post_url, config.build.post_assets_dir),
-        );
-        self.html_content = html_content.replace(
-            &format!("./{}", config.build.post_assets_dir),
-            &format!("{}/{}", post_url, config.build.post_assets_dir),
-        );
+        // Also update the HTML content and summary
+        for html in [&mut self.html_content, &mut self.summary] {
+            *html = html
+                .replace(
+                    &format!("./{}/", config.build.post_assets_dir),
+                    &format!("{}/{}/", post_url, config.build.post_assets_dir),
+                )
+                .replace(
+                    &format!("./{}", config.build.post_assets_dir),
+                    &format!("{}/{}", post_url, config.build.post_assets_dir),
+                );
+        }
     }
 }
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct PostMetadata {
     pub title: String,
-    #[serde(deserialize_with = "validate_date")]
-    pub date: String,
+    pub date: PostDate,
+    // Last revised; filled from git history when `build.updated_from_git` is on
+    #[serde(default)]
+    pub updated: Option<PostDate>,
     #[serde(default = "default_author")]
     pub author: String,
     #[serde(default)]
@@ -94,20 +152,39 @@ pub struct PostMetadata {
     pub slug: String,
     #[serde(default)]
     pub read_time: u32,
+    // Drafts are only built with --drafts and never appear in feeds or the sitemap
+    #[serde(default)]
+    pub draft: bool,
+    // Set to false to leave the post out of sitemap.xml
+    #[serde(default = "default_true")]
+    pub sitemap: bool,
+    // Publish at this URL pattern instead of the site's `permalink`, e.g. "/about-me/"
+    #[serde(
+        default,
+        deserialize_with = "validate_permalink",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub permalink: Option<String>,
+    // Custom values (cover image, series, ...) for templates, as `post.metadata.extra`
+    #[serde(default)]
+    pub extra: BTreeMap<String, Value>,
 }
 
 fn default_author() -> String {
     "Anonymous".to_string()
 }
 
-fn validate_date<'de, D>(deserializer: D) -> Result<String, D::Error>
+fn default_true() -> bool {
+    true
+}
+
+fn validate_permalink<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
 where
     D: serde::Deserializer<'de>,
 {
-    let date_str = String::deserialize(deserializer)?;
-    NaiveDate::parse_from_str(&date_str, "%Y-%m-%d")
-        .map_err(|_| serde::de::Error::custom("Invalid date format. Expected YYYY-MM-DD"))?;
-    Ok(date_str)
+    let pattern = String::deserialize(deserializer)?;
+    permalink::validate(&pattern).map_err(serde::de::Error::custom)?;
+    Ok(Some(pattern))
 }
 
 fn validate_and_slugify<'de, D>(deserializer: D) -> Result<String, D::Error>
@@ -182,7 +259,9 @@ pub async fn create_new_post(
     prompt: Option<String>,
     api_key: Option<String>,
 ) -> Result<PathBuf, Error> {
-    let date = Local::now().format("%Y-%m-%d");
+    let date = Utc::now()
+        .with_timezone(&config.timezone()?)
+        .format("%Y-%m-%d");
     let slug = slugify(title);
 
     if slug.is_empty() {
@@ -267,18 +346,19 @@ mod tests {
     }
 
     #[test]
-    fn test_validate_date() {
+    fn test_post_date() {
         use serde::Deserialize;
 
         #[derive(Deserialize)]
         struct TestDate {
-            #[serde(deserialize_with = "validate_date")]
-            date: String,
+            date: PostDate,
         }
 
-        // Test valid date
+        // Test valid dates, as strings and TOML datetimes
         let valid = toml::from_str::<TestDate>("date = '2024-01-01'").unwrap();
-        assert_eq!(valid.date, "2024-01-01");
+        assert_eq!(valid.date.to_string(), "2024-01-01");
+        let valid = toml::from_str::<TestDate>("date = 2024-01-01T09:30:00+01:00").unwrap();
+        assert_eq!(valid.date.to_string(), "2024-01-01T09:30:00+01:00");
 
         // Test invalid dates
         let invalid_cases = [
@@ -301,6 +381,8 @@ mod tests {
             base_url: "http://test.com".to_string(),
             title: "Test Blog".to_string(),
             description: "Test Description".to_string(),
+            timezone: None,
+            permalink: crate::permalink::DEFAULT.to_string(),
             author: config::Author {
                 name: "Test Author".to_string(),
                 email: "test@example.com".to_string(),
@@ -310,15 +392,27 @@ mod tests {
 
                 output_dir: "dist".to_string(),
                 posts_dir: "posts".to_string(),
+                pages_dir: "pages".to_string(),
                 templates_dir: "templates".to_string(),
+                components_dir: "components".to_string(),
+                syntaxes_dir: "syntaxes".to_string(),
                 static_dir: "static".to_string(),
                 post_assets_dir: "assets".to_string(),
+                paginate_by: None,
+                drafts: false,
+                future: false,
+                cache: true,
+                jobs: None,
+                updated_from_git: false,
             },
             server: config::ServerConfig {
                 auto_build: true,
                 port: 8000,
                 hot_reload: true,
             },
+            feed: config::FeedConfig::default(),
+            markdown: config::MarkdownConfig::default(),
+            extra: Default::default(),
         };
 
         // Create a new post
@@ -344,6 +438,8 @@ mod tests {
             base_url: "http://test.com".to_string(),
             title: "Test Blog".to_string(),
             description: "Test Description".to_string(),
+            timezone: None,
+            permalink: crate::permalink::DEFAULT.to_string(),
             author: config::Author {
                 name: "Test Author".to_string(),
                 email: "test@example.com".to_string(),
@@ -353,15 +449,27 @@ mod tests {
 
                 output_dir: "dist".to_string(),
                 posts_dir: "posts".to_string(),
+                pages_dir: "pages".to_string(),
                 templates_dir: "templates".to_string(),
+                components_dir: "components".to_string(),
+                syntaxes_dir: "syntaxes".to_string(),
                 static_dir: "static".to_string(),
                 post_assets_dir: "assets".to_string(),
+                paginate_by: None,
+                drafts: false,
+                future: false,
+                cache: true,
+                jobs: None,
+                updated_from_git: false,
             },
             server: config::ServerConfig {
                 auto_build: true,
                 port: 8000,
                 hot_reload: true,
             },
+            feed: config::FeedConfig::default(),
+            markdown: config::MarkdownConfig::default(),
+            extra: Default::default(),
         };
 
         let rt = tokio::runtime::Runtime::new().unwrap();
@@ -380,11 +488,10 @@ preview: "Test preview"
 slug: "test-post"
 ---"#;
 
-        let doc: yaml_front_matter::Document<PostMetadata> =
-            yaml_front_matter::YamlFrontMatter::parse(yaml).unwrap();
+        let doc: Document<PostMetadata> = frontmatter::parse("post.md", yaml).unwrap();
 
         assert_eq!(doc.metadata.title, "Test Post");
-        assert_eq!(doc.metadata.date, "2024-01-01");
+        assert_eq!(doc.metadata.date.to_string(), "2024-01-01");
         assert_eq!(doc.metadata.author, "Test Author");
         assert_eq!(doc.metadata.tags, vec!["tag1", "tag2"]);
         assert_eq!(doc.metadata.preview, "Test preview");
@@ -399,12 +506,37 @@ date: "2024-01-01"
 slug: "test-post"
 ---"#;
 
-        let doc: yaml_front_matter::Document<PostMetadata> =
-            yaml_front_matter::YamlFrontMatter::parse(yaml).unwrap();
+        let doc: Document<PostMetadata> = frontmatter::parse("post.md", yaml).unwrap();
 
         assert_eq!(doc.metadata.author, "Anonymous");
         assert!(doc.metadata.tags.is_empty());
         assert_eq!(doc.metadata.preview, "");
+        assert!(doc.metadata.sitemap);
+        assert!(!doc.metadata.draft);
+    }
+
+    #[test]
+    fn test_shortcode_error_reports_file_line() {
+        let content = "---\ntitle: \"Test Post\"\ndate: \"2024-01-01\"\nslug: \"test-post\"\n---\n\nIntro\n\n{{< missing />}}\n";
+
+        let temp_dir = TempDir::new().unwrap();
+        let test_file = temp_dir.path().join("test-post.md");
+        fs::write(&test_file, content).unwrap();
+
+        let err = Post::new_from_path(
+            &test_file,
+            &MarkdownProcessor::new(),
+            Tz::UTC,
+            permalink::DEFAULT,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Shortcode error in {}:9: unknown shortcode `missing` (no components/missing.html found)",
+                test_file.display()
+            )
+        );
     }
 }
 
@@ -425,7 +557,7 @@ We'll keep adding words until we have enough for a proper test of the functional
     fs::write(&test_file, content).unwrap();
 
     let md_proc = MarkdownProcessor::new();
-    let post = Post::new_from_path(&test_file, &md_proc).unwrap();
+    let post = Post::new_from_path(&test_file, &md_proc, Tz::UTC, permalink::DEFAULT).unwrap();
 
     assert!(
         post.metadata.read_time > 0,
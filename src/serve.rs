@@ -1,10 +1,13 @@
 use crate::generator::SiteGenerator;
+use crate::livereload::{self, LiveReload, ReloadKind, LIVE_RELOAD_PATH};
+use crate::overlay::{self, BuildFailure};
 use actix_files::Files;
+use actix_web::dev::Service;
 use actix_web::middleware::Logger;
 use actix_web::{web, App, HttpResponse, HttpServer};
 use notify::{RecursiveMode, Watcher};
 
-use std::sync::mpsc;
+use std::sync::{mpsc, Arc, RwLock};
 use std::time::{Duration, Instant};
 
 use crate::config::Config;
@@ -25,7 +28,12 @@ impl Server {
             self.config.server.port
         );
 
-        if self.config.server.hot_reload {
+        let live_reload = LiveReload::new();
+        let hot_reload = self.config.server.hot_reload;
+        // Set while the last rebuild is broken so pages show the error instead
+        let build_failure: Arc<RwLock<Option<BuildFailure>>> = Arc::new(RwLock::new(None));
+
+        if hot_reload {
             let (tx, rx) = mpsc::channel();
             let config_clone = self.config.clone();
 
@@ -38,7 +46,10 @@ impl Server {
 
             let watch_paths = [
                 config_clone.posts_dir(),
+                config_clone.pages_dir(),
                 config_clone.templates_dir(),
+                config_clone.components_dir(),
+                config_clone.syntaxes_dir(),
                 config_clone.static_dir(),
             ];
 
@@ -49,12 +60,21 @@ impl Server {
             }
 
             let config_for_rebuild = self.config.clone();
+            let live_reload = live_reload.clone();
+            let build_failure = build_failure.clone();
             let _watcher_handler = std::thread::spawn(move || {
                 let mut last_build = Instant::now();
                 let debounce_duration = Duration::from_millis(500);
                 let _watcher = watcher;
 
-                while let Ok(_event) = rx.recv() {
+                while let Ok(event) = rx.recv() {
+                    // Fold the rest of a burst of events (e.g. an editor save)
+                    // into this one so we know everything that changed
+                    let mut changed = event.paths;
+                    while let Ok(event) = rx.try_recv() {
+                        changed.extend(event.paths);
+                    }
+
                     println!("Change detected...");
 
                     if last_build.elapsed() >= debounce_duration {
@@ -65,8 +85,21 @@ impl Server {
                             Ok(_) => {
                                 println!("✨ Site rebuilt successfully!");
                                 last_build = Instant::now();
+
+                                // Recovering from a failure needs a full reload to drop the overlay
+                                let recovered = build_failure.write().unwrap().take().is_some();
+                                live_reload.notify(if recovered {
+                                    ReloadKind::Full
+                                } else {
+                                    ReloadKind::for_changes(&changed)
+                                });
+                            }
+                            Err(e) => {
+                                eprintln!("Build failed: {}", e);
+                                *build_failure.write().unwrap() =
+                                    Some(BuildFailure::from_error(&e));
+                                live_reload.notify(ReloadKind::Full);
                             }
-                            Err(e) => eprintln!("Build failed: {}", e),
                         }
                     }
                 }
@@ -74,10 +107,37 @@ impl Server {
         }
 
         let output_dir = self.config.output_dir();
+        let live_reload = web::Data::new(live_reload);
 
         HttpServer::new(move || {
+            let build_failure = build_failure.clone();
+
             App::new()
+                .app_data(live_reload.clone())
+                .wrap_fn(move |req, srv| {
+                    let failure = build_failure
+                        .read()
+                        .unwrap()
+                        .clone()
+                        .filter(|_| overlay::wants_page(req.path()));
+                    let res = srv.call(req);
+                    async move {
+                        let res = res.await?;
+                        if let Some(failure) = failure {
+                            Ok(res.into_response(
+                                HttpResponse::InternalServerError()
+                                    .content_type("text/html; charset=utf-8")
+                                    .body(overlay::render_overlay(&failure)),
+                            ))
+                        } else if hot_reload {
+                            livereload::inject_into_response(res).await
+                        } else {
+                            Ok(res.map_into_boxed_body())
+                        }
+                    }
+                })
                 .wrap(Logger::default())
+                .route(LIVE_RELOAD_PATH, web::get().to(livereload::events))
                 .service(
                     Files::new("/", output_dir.clone())
                         .index_file("index.html")
@@ -100,15 +160,14 @@ fn rebuild_site(config: &Config) -> Result<(), Error> {
     generator.generate_site()
 }
 
-pub fn serve(config: Config) -> Result<(), Box<dyn std::error::Error>> {
+pub fn serve(config: Config) -> Result<(), Error> {
     let output_dir = config.output_dir();
 
     if !output_dir.exists() {
-        return Err(format!(
+        return Err(Error::Server(format!(
             "Output directory not found: {}. Run `termv build` first.",
             output_dir.display()
-        )
-        .into());
+        )));
     }
 
     let server = Server::new(config);
@@ -0,0 +1,562 @@
+use std::path::Path;
+use tera::{Context, Tera, Value};
+
+use crate::errors::Error;
+
+// A `{{< name key="value" >}}` call found in markdown, optionally wrapping a
+// body closed by `{{< /name >}}`
+#[derive(Debug, PartialEq)]
+pub struct Shortcode<'a> {
+    pub name: &'a str,
+    pub args: Vec<(&'a str, Value)>,
+    pub body: Option<&'a str>,
+    // Byte range of the whole call, closing tag included
+    pub start: usize,
+    pub end: usize,
+}
+
+// Either a shortcode to render or text to pass through as-is
+#[derive(Debug, PartialEq)]
+pub enum Segment<'a> {
+    Text(&'a str),
+    // `{{</* name */>}}` is written out literally, for documenting shortcodes
+    Escaped(String),
+    Shortcode(Shortcode<'a>),
+}
+
+// A problem with a call, at a byte offset into the parsed content
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    pub offset: usize,
+    pub message: String,
+}
+
+const OPEN: &str = "{{<";
+const CLOSE: &str = ">}}";
+
+// Split markdown into text and shortcode calls. Calls in fenced code blocks and
+// `inline code` are left alone; escaped calls are written out everywhere.
+pub fn parse(content: &str) -> Result<Vec<Segment<'_>>, ParseError> {
+    let code_blocks = fenced_code_ranges(content);
+    let code_spans = inline_code_ranges(content, &code_blocks);
+    let in_code = |offset: usize| {
+        code_blocks
+            .iter()
+            .chain(&code_spans)
+            .any(|r| r.contains(&offset))
+    };
+
+    let mut segments = Vec::new();
+    let mut text_start = 0;
+    let mut pos = 0;
+
+    while let Some(found) = content[pos..].find(OPEN) {
+        let start = pos + found;
+        let tag = if in_code(start) {
+            // Code may show anything, including half a tag
+            match tag_at(content, start) {
+                Ok((inner, tag_end)) if escaped(inner).is_some() => (inner, tag_end),
+                _ => {
+                    pos = start + OPEN.len();
+                    continue;
+                }
+            }
+        } else {
+            tag_at(content, start)?
+        };
+
+        let (inner, tag_end) = tag;
+        if text_start < start {
+            segments.push(Segment::Text(&content[text_start..start]));
+        }
+
+        if let Some(escaped) = escaped(inner) {
+            segments.push(Segment::Escaped(format!(
+                "{} {} {}",
+                OPEN,
+                escaped.trim(),
+                CLOSE
+            )));
+            pos = tag_end;
+            text_start = pos;
+            continue;
+        }
+
+        if let Some(name) = inner.strip_prefix('/') {
+            return Err(ParseError {
+                offset: start,
+                message: format!(
+                    "closing tag `{{{{< /{} >}}}}` has no opening tag",
+                    name.trim()
+                ),
+            });
+        }
+
+        // `{{< name ... />}}` never takes a body
+        let (inner, self_closing) = match inner.strip_suffix('/') {
+            Some(inner) => (inner.trim_end(), true),
+            None => (inner, false),
+        };
+
+        let (name, rest) = inner.split_once(char::is_whitespace).unwrap_or((inner, ""));
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(ParseError {
+                offset: start,
+                message: format!("invalid shortcode name `{}`", name),
+            });
+        }
+
+        let args = parse_args(rest).map_err(|message| ParseError {
+            offset: start,
+            message: format!("in `{}`: {}", name, message),
+        })?;
+
+        let (body, end) = if self_closing {
+            (None, tag_end)
+        } else {
+            match find_closing(content, tag_end, name, &in_code)? {
+                Some((body_end, end)) => (Some(&content[tag_end..body_end]), end),
+                None => {
+                    return Err(ParseError {
+                        offset: start,
+                        message: format!(
+                            "`{{{{< {} >}}}}` is never closed; write `{{{{< {} ... />}}}}` for a shortcode without a body",
+                            name, name
+                        ),
+                    })
+                }
+            }
+        };
+
+        segments.push(Segment::Shortcode(Shortcode {
+            name,
+            args,
+            body,
+            start,
+            end,
+        }));
+        pos = end;
+        text_start = pos;
+    }
+
+    if text_start < content.len() {
+        segments.push(Segment::Text(&content[text_start..]));
+    }
+    Ok(segments)
+}
+
+// The trimmed text between `{{<` at `start` and the next `>}}`, and the offset just past it
+fn tag_at(content: &str, start: usize) -> Result<(&str, usize), ParseError> {
+    let inner_start = start + OPEN.len();
+    match content[inner_start..].find(CLOSE) {
+        Some(len) => Ok((
+            content[inner_start..inner_start + len].trim(),
+            inner_start + len + CLOSE.len(),
+        )),
+        None => Err(ParseError {
+            offset: start,
+            message: "shortcode is missing its closing `>}}`".to_string(),
+        }),
+    }
+}
+
+// The call inside a `{{</* name */>}}` tag
+fn escaped(inner: &str) -> Option<&str> {
+    inner
+        .strip_prefix("/*")
+        .and_then(|rest| rest.strip_suffix("*/"))
+}
+
+// Start and end of the `{{< /name >}}` tag closing a call. Calls opened in
+// between have to be closed first, so bodies nest.
+fn find_closing(
+    content: &str,
+    from: usize,
+    name: &str,
+    in_code: &dyn Fn(usize) -> bool,
+) -> Result<Option<(usize, usize)>, ParseError> {
+    let mut open = Vec::new();
+    let mut pos = from;
+    while let Some(found) = content[pos..].find(OPEN) {
+        let start = pos + found;
+        if in_code(start) {
+            pos = start + OPEN.len();
+            continue;
+        }
+
+        let (inner, end) = tag_at(content, start)?;
+        pos = end;
+        if escaped(inner).is_some() {
+            continue;
+        }
+        let Some(closing) = inner.strip_prefix('/').map(str::trim) else {
+            if !inner.ends_with('/') {
+                open.push(inner.split_whitespace().next().unwrap_or(inner));
+            }
+            continue;
+        };
+
+        let message = match open.pop() {
+            Some(inner) if inner == closing => continue,
+            None if closing == name => return Ok(Some((start, end))),
+            Some(inner) => format!(
+                "`{{{{< /{} >}}}}` found while `{{{{< {} >}}}}` is still open; write `{{{{< {} ... />}}}}` for a shortcode without a body",
+                closing, inner, inner
+            ),
+            None => format!(
+                "`{{{{< /{} >}}}}` found while `{{{{< {} >}}}}` is still open",
+                closing, name
+            ),
+        };
+        return Err(ParseError {
+            offset: start,
+            message,
+        });
+    }
+    Ok(None)
+}
+
+// `key="quoted value" count=3 enabled=true` into typed Tera values
+fn parse_args(input: &str) -> Result<Vec<(&str, Value)>, String> {
+    let mut args = Vec::new();
+    let mut rest = input.trim_start();
+
+    while !rest.is_empty() {
+        let key_len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+            .unwrap_or(rest.len());
+        let key = &rest[..key_len];
+        let after_key = &rest[key_len..];
+
+        let Some(value_src) = after_key.strip_prefix('=').filter(|_| !key.is_empty()) else {
+            let token = rest.split_whitespace().next().unwrap_or(rest);
+            return Err(format!(
+                "expected a `key=value` argument, found `{}`",
+                token
+            ));
+        };
+
+        let (value, remaining) = match value_src.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let mut value = String::new();
+                let mut chars = value_src[1..].char_indices();
+                let mut end = None;
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            if let Some((_, escaped)) = chars.next() {
+                                value.push(escaped);
+                            }
+                        }
+                        c if c == quote => {
+                            end = Some(i + 2);
+                            break;
+                        }
+                        c => value.push(c),
+                    }
+                }
+                let end = end.ok_or_else(|| format!("unterminated string for `{}`", key))?;
+                (Value::String(value), &value_src[end..])
+            }
+            _ => {
+                let len = value_src
+                    .find(char::is_whitespace)
+                    .unwrap_or(value_src.len());
+                (bare_value(&value_src[..len]), &value_src[len..])
+            }
+        };
+
+        args.push((key, value));
+        rest = remaining.trim_start();
+    }
+
+    Ok(args)
+}
+
+fn bare_value(raw: &str) -> Value {
+    if let Ok(b) = raw.parse::<bool>() {
+        Value::Bool(b)
+    } else if let Ok(n) = raw.parse::<i64>() {
+        Value::from(n)
+    } else if let Ok(n) = raw.parse::<f64>() {
+        Value::from(n)
+    } else {
+        Value::String(raw.to_string())
+    }
+}
+
+// Byte ranges of ``` and ~~~ fenced code blocks
+fn fenced_code_ranges(content: &str) -> Vec<std::ops::Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut open: Option<(usize, &str)> = None;
+    let mut offset = 0;
+
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim();
+        match open {
+            Some((start, marker)) if trimmed.starts_with(marker) => {
+                ranges.push(start..offset + line.len());
+                open = None;
+            }
+            None if trimmed.starts_with("```") || trimmed.starts_with("~~~") => {
+                open = Some((offset, &trimmed[..3]));
+            }
+            _ => {}
+        }
+        offset += line.len();
+    }
+
+    if let Some((start, _)) = open {
+        ranges.push(start..content.len());
+    }
+    ranges
+}
+
+// Byte ranges of `inline code` spans outside the fenced blocks. A span opened by
+// a run of backticks ends at the next run of the same length in its paragraph.
+fn inline_code_ranges(
+    content: &str,
+    fenced: &[std::ops::Range<usize>],
+) -> Vec<std::ops::Range<usize>> {
+    let bytes = content.as_bytes();
+    let backticks = |at: usize| bytes[at..].iter().take_while(|&&b| b == b'`').count();
+
+    let mut ranges = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        if let Some(block) = fenced.iter().find(|r| r.contains(&pos)) {
+            pos = block.end;
+            continue;
+        }
+        if bytes[pos] == b'\\' {
+            pos += 2;
+            continue;
+        }
+        if bytes[pos] != b'`' {
+            pos += 1;
+            continue;
+        }
+
+        let run = backticks(pos);
+        let limit = paragraph_end(content, pos).min(
+            fenced
+                .iter()
+                .map(|r| r.start)
+                .filter(|&start| start > pos)
+                .min()
+                .unwrap_or(content.len()),
+        );
+        let mut search = pos + run;
+        let mut end = None;
+        while let Some(found) = content[search..limit].find('`') {
+            let close = search + found;
+            let len = backticks(close);
+            if len == run {
+                end = Some(close + len);
+                break;
+            }
+            search = close + len;
+        }
+
+        match end {
+            Some(end) => {
+                ranges.push(pos..end);
+                pos = end;
+            }
+            // Unmatched backticks are just text
+            None => pos += run,
+        }
+    }
+    ranges
+}
+
+// Offset of the blank line ending the paragraph `offset` is in
+fn paragraph_end(content: &str, offset: usize) -> usize {
+    let mut end = content[offset..]
+        .find('\n')
+        .map_or(content.len(), |i| offset + i + 1);
+    for line in content[end..].split_inclusive('\n') {
+        if line.trim().is_empty() {
+            return end;
+        }
+        end += line.len();
+    }
+    content.len()
+}
+
+// Tera templates in the site's `components/` directory, one per shortcode
+#[derive(Default)]
+pub struct Components {
+    tera: Tera,
+}
+
+impl Components {
+    pub fn load(dir: &Path) -> Result<Self, Error> {
+        let tera = if dir.exists() {
+            Tera::new(&format!("{}/**/*.html", dir.display()))?
+        } else {
+            Tera::default()
+        };
+        Ok(Self { tera })
+    }
+
+    // Render a call with its arguments (and `body`, the rendered inner markdown) in scope
+    pub fn render(
+        &self,
+        shortcode: &Shortcode,
+        body_html: Option<String>,
+    ) -> Result<String, String> {
+        let template = format!("{}.html", shortcode.name);
+        if !self.tera.get_template_names().any(|t| t == template) {
+            return Err(format!(
+                "unknown shortcode `{}` (no components/{} found)",
+                shortcode.name, template
+            ));
+        }
+
+        let mut context = Context::new();
+        for (key, value) in &shortcode.args {
+            context.insert(*key, value);
+        }
+        if let Some(body) = body_html {
+            context.insert("body", &body);
+        }
+
+        self.tera.render(&template, &context).map_err(|e| {
+            // Tera keeps the detail (e.g. the missing variable) in the source chain
+            let mut message = e.to_string();
+            let mut source = std::error::Error::source(&e);
+            while let Some(err) = source {
+                message.push_str(": ");
+                message.push_str(&err.to_string());
+                source = err.source();
+            }
+            message
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shortcodes<'a>(segments: &'a [Segment<'a>]) -> Vec<&'a Shortcode<'a>> {
+        segments
+            .iter()
+            .filter_map(|s| match s {
+                Segment::Shortcode(sc) => Some(sc),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_arguments_and_bodies() {
+        let content = r#"Intro {{< youtube id="dQw4w9WgXcQ" start=42 autoplay=false />}}
+
+{{< note title='It\'s "quoted"' >}}
+Some **body**
+{{< /note >}}
+{{< clear />}}"#;
+        let segments = parse(content).unwrap();
+        let calls = shortcodes(&segments);
+
+        assert_eq!(calls.len(), 3);
+        assert_eq!(calls[0].name, "youtube");
+        assert_eq!(
+            calls[0].args,
+            vec![
+                ("id", Value::from("dQw4w9WgXcQ")),
+                ("start", Value::from(42)),
+                ("autoplay", Value::from(false)),
+            ]
+        );
+        assert_eq!(calls[0].body, None);
+
+        assert_eq!(
+            calls[1].args,
+            vec![("title", Value::from("It's \"quoted\""))]
+        );
+        assert_eq!(calls[1].body, Some("\nSome **body**\n"));
+        assert_eq!(calls[2].name, "clear");
+        assert_eq!(segments[0], Segment::Text("Intro "));
+    }
+
+    #[test]
+    fn test_parse_nested_bodies() {
+        let content = "{{< note >}}a {{< note >}}b{{< /note >}} `{{< /note >}}`{{< /note >}} c";
+        let segments = parse(content).unwrap();
+        let calls = shortcodes(&segments);
+
+        assert_eq!(calls.len(), 1);
+        assert_eq!(
+            calls[0].body,
+            Some("a {{< note >}}b{{< /note >}} `{{< /note >}}`")
+        );
+        assert_eq!(segments[1], Segment::Text(" c"));
+    }
+
+    #[test]
+    fn test_parse_skips_code_and_escapes() {
+        let content = "```\n{{< youtube id=\"x\" >}}\n```\n{{</* youtube id=\"x\" */>}}";
+        let segments = parse(content).unwrap();
+
+        assert!(shortcodes(&segments).is_empty());
+        assert_eq!(
+            segments.last(),
+            Some(&Segment::Escaped("{{< youtube id=\"x\" >}}".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_skips_inline_code() {
+        let content = "Write `{{< youtube id=\"x\" >}}` or ``{{< a `b` >}}``, \\`{{< clear />}}\\`";
+        let segments = parse(content).unwrap();
+        let calls = shortcodes(&segments);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].name, "clear");
+
+        // Escapes still work in code, and a lone backtick doesn't hide what follows
+        let segments = parse("`{{</* note */>}}` it's ` {{< clear />}}\n\n`").unwrap();
+        assert_eq!(segments[1], Segment::Escaped("{{< note >}}".to_string()));
+        assert_eq!(shortcodes(&segments).len(), 1);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = parse("text\n{{< youtube id=\"x\"").unwrap_err();
+        assert_eq!(err.offset, 5);
+        assert!(err.message.contains("missing its closing"));
+
+        let err = parse("{{< youtube dQw4w9WgXcQ >}}").unwrap_err();
+        assert!(err.message.contains("expected a `key=value` argument"));
+
+        let err = parse("{{< youtube id=\"x >}}").unwrap_err();
+        assert!(err.message.contains("unterminated string"));
+
+        let err = parse("{{< /note >}}").unwrap_err();
+        assert!(err.message.contains("no opening tag"));
+
+        // A call without `/>}}` takes a body, so it has to be closed
+        let content = "{{< youtube id=\"a\" >}}\n\n{{< youtube id=\"b\" >}}x{{< /youtube >}}";
+        let err = parse(content).unwrap_err();
+        assert_eq!(err.offset, 0);
+        assert!(err.message.contains("`{{< youtube >}}` is never closed"));
+
+        let err = parse("{{< note >}}\n{{< youtube id=\"a\" >}}\n{{< /note >}}").unwrap_err();
+        assert_eq!(err.offset, 36);
+        assert!(err
+            .message
+            .starts_with("`{{< /note >}}` found while `{{< youtube >}}` is still open"));
+
+        let err = parse("{{< note >}}{{< /aside >}}{{< /note >}}").unwrap_err();
+        assert_eq!(
+            err.message,
+            "`{{< /aside >}}` found while `{{< note >}}` is still open"
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use crate::config::Config;
+use crate::feed::escape_xml;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SitemapEntry {
+    // Site-relative URL, e.g. "/posts/foo/"
+    pub path: String,
+    // W3C datetime (YYYY-MM-DD is fine) of the last modification
+    pub lastmod: Option<String>,
+}
+
+impl SitemapEntry {
+    pub fn new(path: impl Into<String>, lastmod: Option<String>) -> Self {
+        Self {
+            path: path.into(),
+            lastmod,
+        }
+    }
+}
+
+pub fn sitemap(config: &Config, entries: &[SitemapEntry]) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+
+    for entry in entries {
+        xml.push_str("<url>\n");
+        xml.push_str(&format!(
+            "<loc>{}</loc>\n",
+            escape_xml(&config.absolute_url(&entry.path))
+        ));
+        if let Some(lastmod) = &entry.lastmod {
+            xml.push_str(&format!("<lastmod>{}</lastmod>\n", escape_xml(lastmod)));
+        }
+        xml.push_str("</url>\n");
+    }
+
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+pub fn robots(config: &Config) -> String {
+    format!(
+        "User-agent: *\nAllow: /\n\nSitemap: {}\n",
+        config.absolute_url("/sitemap.xml")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sitemap_and_robots() {
+        let config = Config {
+            base_url: "https://example.com".to_string(),
+            ..Default::default()
+        };
+        let entries = vec![
+            SitemapEntry::new("/", Some("2024-01-02".to_string())),
+            SitemapEntry::new("/tags/", None),
+        ];
+
+        let xml = sitemap(&config, &entries);
+        assert!(xml.contains(
+            "<url>\n<loc>https://example.com/</loc>\n<lastmod>2024-01-02</lastmod>\n</url>"
+        ));
+        assert!(xml.contains("<url>\n<loc>https://example.com/tags/</loc>\n</url>"));
+
+        assert!(robots(&config).contains("Sitemap: https://example.com/sitemap.xml"));
+    }
+}
@@ -0,0 +1,104 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::post::{slugify, Post};
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Tag {
+    pub name: String,
+    pub slug: String,
+    pub count: usize,
+}
+
+// Group posts by tag, preserving the order of `posts` within each group.
+// Tags that slugify to the same value (e.g. "Rust" and "rust") are merged
+// under the first spelling encountered.
+pub fn collect_tags<'a>(posts: impl IntoIterator<Item = &'a Post>) -> Vec<(Tag, Vec<&'a Post>)> {
+    let mut tags: BTreeMap<String, (String, Vec<&'a Post>)> = BTreeMap::new();
+
+    for post in posts {
+        for name in &post.metadata.tags {
+            let slug = slugify(name);
+            if slug.is_empty() {
+                continue;
+            }
+
+            let (_, tagged) = tags
+                .entry(slug)
+                .or_insert_with(|| (name.trim().to_string(), Vec::new()));
+
+            // Guard against the same tag listed twice on one post
+            if !tagged.iter().any(|p| std::ptr::eq(*p, post)) {
+                tagged.push(post);
+            }
+        }
+    }
+
+    tags.into_iter()
+        .map(|(slug, (name, posts))| {
+            (
+                Tag {
+                    name,
+                    slug,
+                    count: posts.len(),
+                },
+                posts,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::post::PostMetadata;
+
+    fn post_with_tags(slug: &str, tags: &[&str]) -> Post {
+        Post {
+            metadata: PostMetadata {
+                title: slug.to_string(),
+                date: "2024-01-01".parse().unwrap(),
+                updated: None,
+                author: "Test Author".to_string(),
+                tags: tags.iter().map(|t| t.to_string()).collect(),
+                preview: String::new(),
+                slug: slug.to_string(),
+                read_time: 1,
+                draft: false,
+                sitemap: true,
+                permalink: None,
+                extra: Default::default(),
+            },
+            url: format!("/posts/{}/", slug),
+            source: Default::default(),
+            content: String::new(),
+            html_content: String::new(),
+            summary: String::new(),
+            toc: Vec::new(),
+            diagrams: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_collect_tags() {
+        let posts = vec![
+            post_with_tags("first", &["Rust", "web"]),
+            post_with_tags("second", &["rust", "rust"]),
+            post_with_tags("third", &["###"]),
+        ];
+
+        let tags = collect_tags(&posts);
+        assert_eq!(tags.len(), 2);
+
+        let (rust, rust_posts) = &tags[0];
+        assert_eq!(rust.name, "Rust");
+        assert_eq!(rust.slug, "rust");
+        assert_eq!(rust.count, 2);
+        assert_eq!(rust_posts[0].metadata.slug, "first");
+        assert_eq!(rust_posts[1].metadata.slug, "second");
+
+        let (web, _) = &tags[1];
+        assert_eq!(web.slug, "web");
+        assert_eq!(web.count, 1);
+    }
+}